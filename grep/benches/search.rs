@@ -0,0 +1,99 @@
+//times searching a generated corpus with different numbers of threads: `cargo bench`
+use grep::matcher::Matcher;
+use grep::parallel::search_files;
+use grep::regex::Regex;
+use grep::{Config, Searcher};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const FILES: usize = 400;
+const LINES_PER_FILE: usize = 2_000;
+const RUNS: usize = 3;
+const WORDS: [&str; 12] = [
+    "fn", "let", "match", "struct", "impl", "return", "value", "error", "config", "path", "thread",
+    "buffer",
+];
+
+//a few megabytes of word salad, the same every time
+fn generate_corpus(dir: &PathBuf) -> io::Result<Vec<PathBuf>> {
+    fs::create_dir_all(dir)?;
+    let mut seed: u64 = 42;
+    let mut next = || {
+        seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (seed >> 33) as usize
+    };
+    let mut files = Vec::new();
+    for i in 0..FILES {
+        let mut content = String::new();
+        for _ in 0..LINES_PER_FILE {
+            let words = 4 + next() % 8;
+            let line: Vec<&str> = (0..words).map(|_| WORDS[next() % WORDS.len()]).collect();
+            content.push_str(&line.join(" "));
+            content.push_str(&format!(" {}\n", next() % 10_000));
+        }
+        let file = dir.join(format!("file{:04}.txt", i));
+        fs::write(&file, content)?;
+        files.push(file);
+    }
+    Ok(files)
+}
+
+fn best_time(files: &[PathBuf], matcher: &Regex, threads: usize) -> io::Result<(Duration, usize)> {
+    let mut best = Duration::MAX;
+    let mut bytes = 0;
+    let config = Config::default();
+    let searcher = Searcher {
+        matcher: matcher as &dyn Matcher,
+        config: &config,
+        show_names: true,
+        color: false,
+    };
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        let mut output = Vec::new();
+        search_files(
+            files,
+            threads,
+            true,
+            |file, out| searcher.search_file(file, out).map(|_| ()),
+            &mut output,
+            |_, err| Err(err),
+        )?;
+        bytes = output.len();
+        best = best.min(start.elapsed());
+    }
+    Ok((best, bytes))
+}
+
+fn main() -> io::Result<()> {
+    let dir = std::env::temp_dir().join(format!("grep-bench-{}", std::process::id()));
+    let files = generate_corpus(&dir)?;
+    let matcher = Regex::new(r"\bstruct \w+ (error|buffer) \d{3}$", false).unwrap();
+    let available = thread::available_parallelism().map_or(1, |threads| threads.get());
+    let mut thread_counts = vec![1, 2, 4, available];
+    thread_counts.sort_unstable();
+    thread_counts.dedup();
+
+    println!(
+        "{} files of {} lines, best of {} runs",
+        FILES, LINES_PER_FILE, RUNS
+    );
+    let mut single = None;
+    for threads in thread_counts {
+        let (time, bytes) = best_time(&files, &matcher, threads)?;
+        let single = *single.get_or_insert(time);
+        println!(
+            "-j {:<3} {:>8.1} ms  {:>5.2}x  ({} bytes of output)",
+            threads,
+            time.as_secs_f64() * 1000.0,
+            single.as_secs_f64() / time.as_secs_f64(),
+            bytes
+        );
+    }
+    fs::remove_dir_all(dir)
+}
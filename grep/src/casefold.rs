@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+//past the last character with a case
+const LAST_CASED: u32 = 0x1ffff;
+
+//Unicode case folding, worked out from the standard library's case mappings: folding is
+//lowercasing what uppercasing gives, which is what makes `ς`, `σ` and `Σ` or `ſ` and `s` the
+//same. Dotless `ı` is the exception, it only pairs with `I` in Turkish, which is not what
+//folding is for.
+
+//simple folding, one character for one: what `c` is when case does not matter. Characters whose
+//folding takes more than one, like `ß` or `İ`, stay as they are.
+pub fn simple_fold(c: char) -> char {
+    if c == 'ı' {
+        return c;
+    }
+    single(c.to_uppercase())
+        .and_then(|upper| single(upper.to_lowercase()))
+        .or_else(|| single(c.to_lowercase()))
+        .unwrap_or(c)
+}
+
+//full folding, which can give several characters: `ß` is `ss`, `ﬁ` is `fi`
+pub fn full_fold(c: char) -> impl Iterator<Item = char> {
+    //`ı` uppercases to the one `I`, which is put back. `ẞ` is its own uppercase.
+    let dotless = c == 'ı';
+    let c = if c == 'ẞ' { 'ß' } else { c };
+    c.to_uppercase()
+        .flat_map(char::to_lowercase)
+        .map(move |folded| if dotless { c } else { folded })
+}
+
+//all the characters with the same simple folding as `c`, `σ` `Σ` and `ς` for any of them.
+//Empty for one without case.
+pub fn variants(c: char) -> &'static [char] {
+    static CLASSES: OnceLock<HashMap<char, Vec<char>>> = OnceLock::new();
+    let classes = CLASSES.get_or_init(|| {
+        let mut classes: HashMap<char, Vec<char>> = HashMap::new();
+        for c in (0..=LAST_CASED).filter_map(char::from_u32) {
+            let folded = simple_fold(c);
+            if folded != c {
+                let class = classes.entry(folded).or_default();
+                if class.is_empty() {
+                    class.push(folded);
+                }
+                class.push(c);
+            }
+        }
+        classes
+    });
+    classes
+        .get(&simple_fold(c))
+        .map_or(&[], |class| class.as_slice())
+}
+
+fn single(mut chars: impl Iterator<Item = char>) -> Option<char> {
+    let first = chars.next()?;
+    chars.next().is_none().then_some(first)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn full(text: &str) -> String {
+        text.chars().flat_map(full_fold).collect()
+    }
+
+    #[test]
+    fn folds_the_awkward_letters() {
+        //Greek sigma, final sigma and capital sigma are one letter
+        assert_eq!(simple_fold('Σ'), 'σ');
+        assert_eq!(simple_fold('ς'), 'σ');
+        assert_eq!(variants('ς'), ['σ', 'Σ', 'ς']);
+        //sharp s is ss once folded fully, and the same as capital sharp s simply
+        assert_eq!(simple_fold('ẞ'), 'ß');
+        assert_eq!(simple_fold('ß'), 'ß');
+        assert_eq!(full("Straße"), "strasse");
+        assert_eq!(full("STRAẞE"), "strasse");
+        //dotless i and dotted capital I do not fold to an ASCII i
+        assert_eq!(simple_fold('ı'), 'ı');
+        assert_eq!(simple_fold('I'), 'i');
+        assert_eq!(simple_fold('İ'), 'İ');
+        assert_eq!(full("İı"), "i\u{307}ı");
+        assert!(!variants('i').contains(&'ı'));
+        //symbols that fold to letters
+        assert_eq!(simple_fold('K'), 'k');
+        assert_eq!(simple_fold('µ'), 'μ');
+        assert_eq!(full("ﬁ"), "fi");
+        assert!(variants('7').is_empty());
+    }
+}
@@ -0,0 +1,520 @@
+use crate::error::ArgsError;
+use crate::matcher::{FixedMatcher, Matcher, MultiMatcher, WordMatcher};
+use crate::regex::{self, Regex};
+use crate::walk::WalkOptions;
+use crate::STDIN_PATH;
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::thread;
+
+pub const USAGE: &str = "\
+usage: grep [OPTIONS] PATTERN [PATH...]
+       grep [OPTIONS] -e PATTERN... [PATH...]
+Prints the lines matching PATTERN, a regular expression. A PATH is a file or a
+directory searched recursively, leaving out hidden files and whatever .gitignore
+or .ignore files exclude. Without a PATH, or with `-`, stdin is searched.
+Exits with 0 if a line matched, 1 if none did and 2 on errors.
+
+matching:
+  -e, --regexp PATTERN   search for PATTERN, can be given more than once
+  -f, --file FILE        search for the patterns in FILE, one per line
+  -F, --fixed-strings    take the patterns as plain strings
+  -i, --ignore-case      ignore case
+  -s, --case-sensitive   match case, the default
+  -S, --smart-case       ignore case unless a pattern has upper case letters
+  -v, --invert-match     select the lines that do not match
+  -w, --word-regexp      only match whole words
+  -x, --line-regexp      only match whole lines
+output:
+  -c, --count            print how many lines matched in each file
+  -l, --files-with-matches     only print the names of files with a match
+  -L, --files-without-match    only print the names of files without one
+  -n, --line-number      print the line number before each line
+      --column           print the column of the first match too
+      --color WHEN       highlight matches: auto (on a terminal), always or never
+      --json             print each matching line as a JSON object
+      --trim             leave out the whitespace around printed lines
+      --replace TEXT     print lines with each match replaced by TEXT, in which $1 or
+                         ${1} is what group 1 matched and $0 the whole match
+      --in-place         write the replaced lines back to the files rather than print them
+      --dry-run          with --in-place, print a diff of the changes and leave the files
+  -A, --after-context N  print N lines after each match
+  -B, --before-context N print N lines before each match
+  -C, --context N        print N lines before and after each match
+files:
+  -g, --glob GLOB        only search files matching GLOB, or not matching `!GLOB`
+  -t, --type TYPE        only search files of a type (rust, toml, md, ...)
+      --hidden           also search hidden files and directories
+      --no-ignore        do not read .gitignore and .ignore files
+  -j, --threads N        search N files at once, all cores by default
+      --sort path        print files in path order rather than as they start
+  -h, --help             print this help";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Case {
+    Sensitive,
+    Insensitive,
+    //insensitive for a pattern that is all lower case
+    Smart,
+}
+
+//what is printed for each file searched
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Output {
+    Lines,
+    Count,
+    FilesWithMatches,
+    FilesWithoutMatch,
+}
+
+//--color
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ColorChoice {
+    //when printing to a terminal that can show colors
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                io::stdout().is_terminal()
+                    && env::var_os("NO_COLOR").is_none()
+                    && env::var("TERM").map_or(true, |term| term != "dumb")
+            }
+        }
+    }
+}
+
+pub struct Config {
+    //a line matches if any of them matches
+    pub patterns: Vec<String>,
+    //files and directories to search, directories recursively
+    pub paths: Vec<String>,
+    pub case: Case,
+    //-F: the patterns are plain strings, not regular expressions
+    pub is_fixed_string: bool,
+    pub invert_match: bool,
+    pub word_regexp: bool,
+    pub line_regexp: bool,
+    pub output: Output,
+    pub line_number: bool,
+    pub column: bool,
+    pub color: ColorChoice,
+    pub json: bool,
+    //only changes how lines look, match offsets are still in the untrimmed line
+    pub trim: bool,
+    //--replace: what a match is replaced with
+    pub replace: Option<String>,
+    pub in_place: bool,
+    pub dry_run: bool,
+    //-B and -A: lines printed around each match
+    pub before_context: usize,
+    pub after_context: usize,
+    pub walk: WalkOptions,
+    //-j: how many files are searched at once
+    pub threads: usize,
+    //--sort path: files are printed in the order they were found rather than as they start
+    pub sort_by_path: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            patterns: Vec::new(),
+            paths: vec![STDIN_PATH.to_string()],
+            case: Case::Sensitive,
+            is_fixed_string: false,
+            invert_match: false,
+            word_regexp: false,
+            line_regexp: false,
+            output: Output::Lines,
+            line_number: false,
+            column: false,
+            color: ColorChoice::Auto,
+            json: false,
+            trim: false,
+            replace: None,
+            in_place: false,
+            dry_run: false,
+            before_context: 0,
+            after_context: 0,
+            walk: WalkOptions::default(),
+            threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+            sort_by_path: false,
+        }
+    }
+}
+
+impl Config {
+    //`args[0]` is the program name. Short options can be grouped (`-in`), and take their value
+    //from the rest of the group (`-j4`) or the next argument; long ones as `--glob=*.rs` or
+    //`--glob *.rs`. Everything after `--` is a pattern or a path.
+    pub fn new(args: &[String]) -> Result<Config, ArgsError> {
+        let mut config = Config::default();
+        let mut explicit_patterns = false;
+        let mut positional = Vec::new();
+        let mut args = args.iter().skip(1);
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                positional.extend(args.by_ref().cloned());
+                break;
+            }
+            if let Some(long) = arg.strip_prefix("--") {
+                let (name, inline_value) = match long.split_once('=') {
+                    Some((name, value)) => (name, Some(value.to_string())),
+                    None => (long, None),
+                };
+                let option = format!("--{}", name);
+                let takes_value = config.apply(&option, None, &mut explicit_patterns)?;
+                if takes_value {
+                    let value = inline_value
+                        .or_else(|| args.next().cloned())
+                        .ok_or_else(|| ArgsError::MissingValue(option.clone()))?;
+                    config.apply(&option, Some(value), &mut explicit_patterns)?;
+                } else if inline_value.is_some() {
+                    return Err(ArgsError::UnknownOption(arg.clone()));
+                }
+                continue;
+            }
+            let group = match arg.strip_prefix('-') {
+                Some(group) if !group.is_empty() => group,
+                _ => {
+                    positional.push(arg.clone());
+                    continue;
+                }
+            };
+            for (i, c) in group.char_indices() {
+                let option = format!("-{}", c);
+                if config.apply(&option, None, &mut explicit_patterns)? {
+                    let rest = &group[i + c.len_utf8()..];
+                    let value = if rest.is_empty() {
+                        args.next()
+                            .cloned()
+                            .ok_or_else(|| ArgsError::MissingValue(option.clone()))?
+                    } else {
+                        rest.to_string()
+                    };
+                    config.apply(&option, Some(value), &mut explicit_patterns)?;
+                    break;
+                }
+            }
+        }
+
+        let mut positional = positional.into_iter();
+        if !explicit_patterns {
+            config
+                .patterns
+                .push(positional.next().ok_or(ArgsError::NoPattern)?);
+        }
+        let paths: Vec<String> = positional.collect();
+        if !paths.is_empty() {
+            config.paths = paths;
+        }
+        config.check()?;
+        Ok(config)
+    }
+
+    //options that only work together
+    fn check(&self) -> Result<(), ArgsError> {
+        let requires = |option: &str, required: &str| {
+            Err(ArgsError::Requires {
+                option: option.to_string(),
+                required: required.to_string(),
+            })
+        };
+        if self.in_place && self.replace.is_none() {
+            return requires("--in-place", "--replace");
+        }
+        if self.dry_run && !self.in_place {
+            return requires("--dry-run", "--in-place");
+        }
+        if self.in_place && self.paths.iter().any(|path| path == STDIN_PATH) {
+            return requires("--in-place", "files to rewrite, not stdin");
+        }
+        Ok(())
+    }
+
+    //sets what `option` stands for. Without a `value` for one that needs it, nothing is set
+    //and true returned so the caller can go and get the value.
+    fn apply(
+        &mut self,
+        option: &str,
+        value: Option<String>,
+        explicit_patterns: &mut bool,
+    ) -> Result<bool, ArgsError> {
+        let takes_value = matches!(
+            option,
+            "-e" | "--regexp"
+                | "-f"
+                | "--file"
+                | "-g"
+                | "--glob"
+                | "-t"
+                | "--type"
+                | "-j"
+                | "--threads"
+                | "--sort"
+                | "--color"
+                | "--colour"
+                | "--replace"
+                | "-A"
+                | "--after-context"
+                | "-B"
+                | "--before-context"
+                | "-C"
+                | "--context"
+        );
+        let value = match (takes_value, value) {
+            (true, None) => return Ok(true),
+            (_, value) => value.unwrap_or_default(),
+        };
+        let invalid = |value: String| ArgsError::InvalidValue {
+            option: option.to_string(),
+            value,
+        };
+        let lines = |value: String| value.parse::<usize>().map_err(|_| invalid(value));
+        match option {
+            "-h" | "--help" => return Err(ArgsError::Help),
+            "-e" | "--regexp" => {
+                self.patterns.push(value);
+                *explicit_patterns = true;
+            }
+            "-f" | "--file" => {
+                let contents =
+                    fs::read_to_string(&value).map_err(|source| ArgsError::PatternFile {
+                        path: value.clone(),
+                        source,
+                    })?;
+                self.patterns.extend(contents.lines().map(String::from));
+                *explicit_patterns = true;
+            }
+            "-F" | "--fixed-strings" => self.is_fixed_string = true,
+            "-i" | "--ignore-case" => self.case = Case::Insensitive,
+            "-s" | "--case-sensitive" => self.case = Case::Sensitive,
+            "-S" | "--smart-case" => self.case = Case::Smart,
+            "-v" | "--invert-match" => self.invert_match = true,
+            "-w" | "--word-regexp" => self.word_regexp = true,
+            "-x" | "--line-regexp" => self.line_regexp = true,
+            "-c" | "--count" => self.output = Output::Count,
+            "-l" | "--files-with-matches" => self.output = Output::FilesWithMatches,
+            "-L" | "--files-without-match" => self.output = Output::FilesWithoutMatch,
+            "-n" | "--line-number" => self.line_number = true,
+            //the first match being where to look, a column makes no sense without a line
+            "--column" => {
+                self.column = true;
+                self.line_number = true;
+            }
+            "--color" | "--colour" => {
+                self.color = match value.as_str() {
+                    "auto" => ColorChoice::Auto,
+                    "always" => ColorChoice::Always,
+                    "never" => ColorChoice::Never,
+                    _ => return Err(invalid(value)),
+                }
+            }
+            "--json" => self.json = true,
+            "--trim" => self.trim = true,
+            "--replace" => self.replace = Some(value),
+            "--in-place" => self.in_place = true,
+            "--dry-run" => self.dry_run = true,
+            "-A" | "--after-context" => self.after_context = lines(value)?,
+            "-B" | "--before-context" => self.before_context = lines(value)?,
+            "-C" | "--context" => {
+                self.after_context = lines(value)?;
+                self.before_context = self.after_context;
+            }
+            "-g" | "--glob" => self.walk.globs.push(value),
+            "-t" | "--type" => {
+                if !self.walk.add_type(&value) {
+                    return Err(ArgsError::UnknownFileType(value));
+                }
+            }
+            "--hidden" => self.walk.hidden = true,
+            "--no-ignore" => self.walk.no_ignore = true,
+            "-j" | "--threads" => {
+                self.threads = value
+                    .parse()
+                    .ok()
+                    .filter(|threads| *threads > 0)
+                    .ok_or_else(|| invalid(value))?;
+            }
+            "--sort" => match value.as_str() {
+                "path" => self.sort_by_path = true,
+                "none" => self.sort_by_path = false,
+                _ => return Err(invalid(value)),
+            },
+            _ => return Err(ArgsError::UnknownOption(option.to_string())),
+        }
+        Ok(false)
+    }
+
+    //one matcher for all the patterns, a line matching if any of them does
+    pub fn matcher(&self) -> Result<Box<dyn Matcher>, regex::Error> {
+        let mut matchers = Vec::new();
+        for pattern in &self.patterns {
+            let case_insensitive = match self.case {
+                Case::Sensitive => false,
+                Case::Insensitive => true,
+                Case::Smart => !has_upper_case(pattern, self.is_fixed_string),
+            };
+            let mut matcher: Box<dyn Matcher> = if self.line_regexp {
+                let source = if self.is_fixed_string {
+                    regex::escape(pattern)
+                } else {
+                    //checked on its own first so errors point into what the user wrote
+                    Regex::new(pattern, case_insensitive)?;
+                    pattern.clone()
+                };
+                Box::new(Regex::new(&format!("^(?:{})$", source), case_insensitive)?)
+            } else if self.is_fixed_string {
+                Box::new(FixedMatcher::new(pattern, case_insensitive))
+            } else {
+                Box::new(Regex::new(pattern, case_insensitive)?)
+            };
+            if self.word_regexp {
+                matcher = Box::new(WordMatcher::new(matcher));
+            }
+            matchers.push(matcher);
+        }
+        Ok(if matchers.len() == 1 {
+            matchers.remove(0)
+        } else {
+            Box::new(MultiMatcher::new(matchers))
+        })
+    }
+}
+
+//upper case letters that stand for themselves, so `\W` or `\S` do not count
+fn has_upper_case(pattern: &str, is_fixed_string: bool) -> bool {
+    if is_fixed_string {
+        return pattern.chars().any(char::is_uppercase);
+    }
+    let mut escaped = false;
+    for c in pattern.chars() {
+        if !escaped && c.is_uppercase() {
+            return true;
+        }
+        escaped = !escaped && c == '\\';
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Config, ArgsError> {
+        let args: Vec<String> = std::iter::once("grep")
+            .chain(args.split_whitespace())
+            .map(String::from)
+            .collect();
+        Config::new(&args)
+    }
+
+    #[test]
+    fn parses_flags_patterns_and_paths() {
+        let config = parse("-inw -j2 --sort=path fn src tests").unwrap();
+        assert_eq!(config.patterns, ["fn"]);
+        assert_eq!(config.paths, ["src", "tests"]);
+        assert_eq!(config.case, Case::Insensitive);
+        assert!(config.line_number && config.word_regexp && config.sort_by_path);
+        assert_eq!(config.threads, 2);
+
+        let config = parse("-e foo --regexp bar -c -s -- -v").unwrap();
+        assert_eq!(config.patterns, ["foo", "bar"]);
+        assert_eq!(config.paths, ["-v"]);
+        assert_eq!(config.output, Output::Count);
+        assert_eq!(config.case, Case::Sensitive);
+
+        let config = parse("-S -efoo -l").unwrap();
+        assert_eq!(config.patterns, ["foo"]);
+        assert_eq!(config.paths, [STDIN_PATH]);
+        assert_eq!(config.output, Output::FilesWithMatches);
+
+        let config = parse("-C3 -A 1 x").unwrap();
+        assert_eq!((config.before_context, config.after_context), (3, 1));
+        assert_eq!(parse("--context=2 x").unwrap().after_context, 2);
+
+        let config = parse("--replace=$1 --in-place --dry-run x src").unwrap();
+        assert_eq!(config.replace.as_deref(), Some("$1"));
+        assert!(config.in_place && config.dry_run);
+
+        let config = parse("--color=never --column --json --trim x").unwrap();
+        assert_eq!(config.color, ColorChoice::Never);
+        assert!(config.column && config.line_number && config.json && config.trim);
+    }
+
+    #[test]
+    fn reports_what_is_wrong() {
+        assert!(matches!(parse("--help"), Err(ArgsError::Help)));
+        assert!(matches!(parse("-ih"), Err(ArgsError::Help)));
+        assert!(matches!(parse(""), Err(ArgsError::NoPattern)));
+        assert!(matches!(parse("-q x"), Err(ArgsError::UnknownOption(option)) if option == "-q"));
+        assert!(matches!(
+            parse("--hidden=yes x"),
+            Err(ArgsError::UnknownOption(_))
+        ));
+        assert!(matches!(parse("x -e"), Err(ArgsError::MissingValue(option)) if option == "-e"));
+        assert!(matches!(
+            parse("-j none x"),
+            Err(ArgsError::InvalidValue { option, value }) if option == "-j" && value == "none"
+        ));
+        assert!(matches!(
+            parse("--in-place x src"),
+            Err(ArgsError::Requires { option, .. }) if option == "--in-place"
+        ));
+        assert!(matches!(
+            parse("--replace y --dry-run x src"),
+            Err(ArgsError::Requires { .. })
+        ));
+        assert!(matches!(
+            parse("--replace y --in-place x"),
+            Err(ArgsError::Requires { .. })
+        ));
+        assert!(matches!(
+            parse("--color sometimes x"),
+            Err(ArgsError::InvalidValue { .. })
+        ));
+        assert!(matches!(
+            parse("-A -1 x"),
+            Err(ArgsError::InvalidValue { .. })
+        ));
+        assert!(matches!(
+            parse("-t cobol x"),
+            Err(ArgsError::UnknownFileType(_))
+        ));
+        assert!(matches!(
+            parse("-f /no/such/patterns"),
+            Err(ArgsError::PatternFile { .. })
+        ));
+    }
+
+    #[test]
+    fn builds_matchers_for_the_options() {
+        let matches =
+            |args: &str, line: &str| parse(args).unwrap().matcher().unwrap().is_match(line);
+        assert!(!matches("Main fn main()", "main"));
+        assert!(matches("-i Main fn main()", "fn main()"));
+        assert!(matches("-S main fn Main()", "fn Main()"));
+        assert!(!matches("-S Main fn main()", "fn main()"));
+        assert!(matches("-S \\Sbc", "ABC"));
+        assert!(matches("-w main", "fn main()"));
+        assert!(!matches("-w main", "fn domain()"));
+        assert!(matches("-x a|ab", "ab"));
+        assert!(!matches("-x ab", "abc"));
+        assert!(matches("-Fx a.b", "a.b"));
+        assert!(!matches("-Fx a.b", "axb"));
+        assert!(matches("-e cat -e dog", "hotdog"));
+        assert!(!matches("-e cat -e dog", "bird"));
+        assert_eq!(
+            parse("-x a)(b").unwrap().matcher().err().unwrap().position,
+            1
+        );
+    }
+}
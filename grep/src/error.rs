@@ -0,0 +1,82 @@
+use crate::config::USAGE;
+use crate::regex;
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+//what is wrong with the command line
+#[derive(Debug)]
+pub enum ArgsError {
+    //--help, not an error as such but it ends parsing the same way
+    Help,
+    UnknownOption(String),
+    MissingValue(String),
+    InvalidValue { option: String, value: String },
+    UnknownFileType(String),
+    //an option only makes sense with something else
+    Requires { option: String, required: String },
+    NoPattern,
+    PatternFile { path: String, source: io::Error },
+}
+
+impl fmt::Display for ArgsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArgsError::Help => write!(f, "{}", USAGE),
+            ArgsError::UnknownOption(option) => write!(f, "unknown option {}", option),
+            ArgsError::MissingValue(option) => write!(f, "{} needs a value", option),
+            ArgsError::InvalidValue { option, value } => {
+                write!(f, "invalid value {:?} for {}", value, option)
+            }
+            ArgsError::UnknownFileType(name) => write!(f, "unknown file type {}", name),
+            ArgsError::Requires { option, required } => write!(f, "{} needs {}", option, required),
+            ArgsError::NoPattern => write!(f, "no pattern given, see --help"),
+            ArgsError::PatternFile { path, source } => write!(f, "{}: {}", path, source),
+        }
+    }
+}
+
+impl StdError for ArgsError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            ArgsError::PatternFile { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+//what can stop a search as a whole. A file that cannot be read is only reported.
+#[derive(Debug)]
+pub enum Error {
+    Pattern(regex::Error),
+    //a path given on the command line, or a directory under it, could not be listed
+    Path { path: PathBuf, source: io::Error },
+    Output(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Pattern(err) => write!(f, "invalid pattern: {}", err),
+            Error::Path { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Output(err) => write!(f, "cannot write output: {}", err),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Error::Pattern(err) => Some(err),
+            Error::Path { source, .. } => Some(source),
+            Error::Output(err) => Some(err),
+        }
+    }
+}
+
+impl From<regex::Error> for Error {
+    fn from(err: regex::Error) -> Self {
+        Error::Pattern(err)
+    }
+}
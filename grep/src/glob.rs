@@ -0,0 +1,112 @@
+//shell style wildcards over `/` separated paths: `*` is any run of characters but `/`,
+//`**` any run including `/` (`**/` also matching no directory at all), `?` one character
+//other than `/`, and `[abc]`, `[a-z]`, `[!abc]` or `[^abc]` one character from a set
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let mut memo = vec![None; (pattern.len() + 1) * (text.len() + 1)];
+    matches_from(&pattern, &text, 0, 0, &mut memo)
+}
+
+fn matches_from(
+    pattern: &[char],
+    text: &[char],
+    p: usize,
+    t: usize,
+    memo: &mut Vec<Option<bool>>,
+) -> bool {
+    let key = p * (text.len() + 1) + t;
+    if let Some(result) = memo[key] {
+        return result;
+    }
+    let result = match pattern.get(p) {
+        None => t == text.len(),
+        Some('*') if pattern.get(p + 1) == Some(&'*') => {
+            let rest = p + 2;
+            (pattern.get(rest) == Some(&'/') && matches_from(pattern, text, rest + 1, t, memo))
+                || (t..=text.len()).any(|end| matches_from(pattern, text, rest, end, memo))
+        }
+        Some('*') => (t..=text.len())
+            .take_while(|end| *end == t || text[end - 1] != '/')
+            .any(|end| matches_from(pattern, text, p + 1, end, memo)),
+        Some('?') => {
+            t < text.len() && text[t] != '/' && matches_from(pattern, text, p + 1, t + 1, memo)
+        }
+        Some('[') => match (text.get(t), class_end(pattern, p)) {
+            (Some(c), Some(end)) => {
+                *c != '/'
+                    && class_matches(&pattern[p + 1..end], *c)
+                    && matches_from(pattern, text, end + 1, t + 1, memo)
+            }
+            (Some(c), None) => *c == '[' && matches_from(pattern, text, p + 1, t + 1, memo),
+            (None, _) => false,
+        },
+        Some('\\') if p + 1 < pattern.len() => {
+            text.get(t) == Some(&pattern[p + 1]) && matches_from(pattern, text, p + 2, t + 1, memo)
+        }
+        Some(c) => text.get(t) == Some(c) && matches_from(pattern, text, p + 1, t + 1, memo),
+    };
+    memo[key] = Some(result);
+    result
+}
+
+//the index of the `]` closing the class opened at `open`, a `]` right after the opening
+//(or after its `!`) being part of the set
+fn class_end(pattern: &[char], open: usize) -> Option<usize> {
+    let mut i = open + 1;
+    if matches!(pattern.get(i), Some('!' | '^')) {
+        i += 1;
+    }
+    if pattern.get(i) == Some(&']') {
+        i += 1;
+    }
+    pattern[i..]
+        .iter()
+        .position(|c| *c == ']')
+        .map(|found| i + found)
+}
+
+fn class_matches(class: &[char], c: char) -> bool {
+    let (negated, class) = match class.first() {
+        Some('!' | '^') => (true, &class[1..]),
+        _ => (false, class),
+    };
+    let mut found = false;
+    let mut i = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == '-' {
+            found |= (class[i]..=class[i + 2]).contains(&c);
+            i += 3;
+        } else {
+            found |= class[i] == c;
+            i += 1;
+        }
+    }
+    found != negated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_wildcards_and_classes() {
+        assert!(glob_match("*.rs", "main.rs"));
+        assert!(!glob_match("*.rs", "src/main.rs"));
+        assert!(glob_match("src/*.rs", "src/main.rs"));
+        assert!(glob_match("**/*.rs", "main.rs"));
+        assert!(glob_match("**/*.rs", "a/b/main.rs"));
+        assert!(glob_match("a/**/b", "a/b"));
+        assert!(glob_match("a/**/b", "a/x/y/b"));
+        assert!(glob_match("target/**", "target/debug/grep"));
+        assert!(glob_match("file?.txt", "file1.txt"));
+        assert!(!glob_match("file?.txt", "file10.txt"));
+        assert!(glob_match("[a-c]x", "bx"));
+        assert!(glob_match("[!a-c]x", "dx"));
+        assert!(!glob_match("[!a-c]x", "ax"));
+        assert!(glob_match("[]]", "]"));
+        assert!(glob_match("[x", "[x"));
+        assert!(glob_match("\\*", "*"));
+        assert!(!glob_match("\\*", "a"));
+    }
+}
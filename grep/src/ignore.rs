@@ -0,0 +1,119 @@
+use crate::glob::glob_match;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+//the files whose patterns say what not to search, .ignore winning over .gitignore
+pub const IGNORE_FILES: [&str; 2] = [".gitignore", ".ignore"];
+
+//one line of an ignore file
+#[derive(Clone)]
+struct Rule {
+    pattern: String,
+    //`!pattern` brings back what an earlier rule ignored
+    negated: bool,
+    //`pattern/` only matches directories
+    dir_only: bool,
+    //a pattern with a `/` other than at the end is relative to the ignore file's directory,
+    //one without matches the name at any depth
+    anchored: bool,
+}
+
+//the rules of one ignore file and the directory they are relative to
+#[derive(Clone)]
+pub struct IgnoreFile {
+    dir: PathBuf,
+    rules: Vec<Rule>,
+}
+
+impl IgnoreFile {
+    pub fn read(dir: &Path, name: &str) -> Option<Self> {
+        let contents = fs::read_to_string(dir.join(name)).ok()?;
+        Some(Self::parse(dir, &contents))
+    }
+
+    fn parse(dir: &Path, contents: &str) -> Self {
+        let mut rules = Vec::new();
+        for line in contents.lines() {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (negated, line) = match line.strip_prefix('!') {
+                Some(line) => (true, line),
+                None => (false, line.strip_prefix('\\').unwrap_or(line)),
+            };
+            let (dir_only, line) = match line.strip_suffix('/') {
+                Some(line) => (true, line),
+                None => (false, line),
+            };
+            let anchored = line.contains('/');
+            rules.push(Rule {
+                pattern: line.trim_start_matches('/').to_string(),
+                negated,
+                dir_only,
+                anchored,
+            });
+        }
+        Self {
+            dir: dir.to_path_buf(),
+            rules,
+        }
+    }
+
+    //Some(true) if the last rule matching `path` ignores it, Some(false) if it brings it back,
+    //None if no rule is about it
+    pub fn matches(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let relative = path.strip_prefix(&self.dir).ok()?;
+        let relative = relative.to_string_lossy().replace('\\', "/");
+        let name = relative.rsplit('/').next().unwrap_or_default();
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| {
+                (is_dir || !rule.dir_only)
+                    && if rule.anchored {
+                        glob_match(&rule.pattern, &relative)
+                    } else {
+                        glob_match(&rule.pattern, name)
+                    }
+            })
+            .map(|rule| !rule.negated)
+    }
+}
+
+//whether the innermost rule about `path` among `files`, outermost first, ignores it
+pub fn is_ignored(files: &[IgnoreFile], path: &Path, is_dir: bool) -> bool {
+    files
+        .iter()
+        .rev()
+        .find_map(|file| file.matches(path, is_dir))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn later_and_deeper_rules_win() {
+        let root = IgnoreFile::parse(
+            Path::new("/repo"),
+            "# build output\n/target\n*.log\n!keep.log\nbuild/\ndocs/*.html\n",
+        );
+        let sub = IgnoreFile::parse(Path::new("/repo/sub"), "!debug.log\n");
+        let files = [root, sub];
+        let ignored = |path: &str, is_dir| is_ignored(&files, Path::new(path), is_dir);
+
+        assert!(ignored("/repo/target", true));
+        assert!(!ignored("/repo/sub/target", true));
+        assert!(ignored("/repo/a/b/trace.log", false));
+        assert!(!ignored("/repo/keep.log", false));
+        assert!(!ignored("/repo/sub/debug.log", false));
+        assert!(ignored("/repo/debug.log", false));
+        assert!(ignored("/repo/src/build", true));
+        assert!(!ignored("/repo/src/build", false));
+        assert!(ignored("/repo/docs/index.html", false));
+        assert!(!ignored("/repo/other/docs/index.html", false));
+        assert!(!ignored("/elsewhere/a.log", false));
+    }
+}
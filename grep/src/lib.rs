@@ -1,70 +1,230 @@
-use std::error::Error;
-use std::{fs, env};
-pub struct Config {
-    pub path: String,
-    pub pattern: String,
-    pub is_case_sensitive: bool,
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+pub mod casefold;
+pub mod config;
+pub mod error;
+pub mod glob;
+pub mod ignore;
+pub mod matcher;
+pub mod parallel;
+pub mod printer;
+pub mod reader;
+pub mod regex;
+pub mod replace;
+pub mod walk;
+
+pub use config::Config;
+use config::Output;
+pub use error::{ArgsError, Error};
+use matcher::{FixedMatcher, Matcher};
+use printer::Printer;
+use reader::LineReader;
+
+//the path standing for stdin, and how its lines are labelled
+pub const STDIN_PATH: &str = "-";
+const STDIN_NAME: &str = "(standard input)";
+
+//a selected line as it is in its file, and where it is
+#[derive(Debug, PartialEq)]
+pub struct Match<'a> {
+    //counting from 1
+    pub line_number: usize,
+    pub line: &'a str,
+    //byte ranges of the matches in `line`
+    pub ranges: Vec<Range<usize>>,
 }
 
-impl Config {
-    pub fn new(args: &[String]) -> Result<Config, &'static str> {
-        if args.len() < 3 {
-            return Err("Not enough arguments");
-        }
+//how a search went, for the exit status
+pub struct Summary {
+    //a line was selected (for -L: a file was listed)
+    pub matched: bool,
+    //files and directories that could not be read, each already reported on stderr
+    pub errors: usize,
+}
 
-        let path = &args[1].clone();
-        let pattern = &args[2].clone();
-        let is_case_sensitive = env::var("CASE_SENSITIVE").is_ok();
-        Ok(Config {
-            path: path.to_string(),
-            pattern: pattern.to_string(),
-            is_case_sensitive
-        })
+pub fn run(config: Config) -> Result<Summary, Error> {
+    let matcher = config.matcher()?;
+    let mut errors = 0;
+    let files = walk::files(&config.paths, &config.walk, &mut |dir, err| {
+        eprintln!("grep: {}: {}", dir.display(), err);
+        errors += 1;
+    })?;
+    //a single file searched on its own is the only case where it is obvious where lines are from
+    let show_names = config.paths.len() > 1 || Path::new(&config.paths[0]).is_dir();
+    let searcher = Searcher {
+        matcher: matcher.as_ref(),
+        config: &config,
+        show_names,
+        color: config.color.enabled(),
+    };
+    let matched = AtomicBool::new(false);
+    let mut out = BufWriter::new(io::stdout().lock());
+    let search = |file: &Path, out: &mut dyn Write| {
+        let found = if config.in_place {
+            searcher.replace_file(file, out)?
+        } else {
+            searcher.search_file(file, out)?
+        };
+        if found {
+            matched.store(true, Ordering::Relaxed);
+        }
+        Ok(())
+    };
+    let (threads, sorted) = (config.threads, config.sort_by_path);
+    let result = parallel::search_files(&files, threads, sorted, search, &mut out, |file, err| {
+        //there is no point going on once nobody reads the output
+        if err.kind() == io::ErrorKind::BrokenPipe {
+            return Err(err);
+        }
+        eprintln!("grep: {}: {}", file.display(), err);
+        errors += 1;
+        Ok(())
+    })
+    .and_then(|()| out.flush());
+    match result {
+        Err(err) if err.kind() != io::ErrorKind::BrokenPipe => return Err(Error::Output(err)),
+        _ => (),
     }
+    Ok(Summary {
+        matched: matched.into_inner(),
+        errors,
+    })
 }
 
-pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let content = fs::read_to_string(config.path)?;
-    let res: Vec<&str> = if config.is_case_sensitive {
-        search(&config.pattern, &content)
-    } else {
-        case_insensitive_search(&config.pattern, &content)
-    };
+//searches files the way the command line says
+pub struct Searcher<'a> {
+    pub matcher: &'a dyn Matcher,
+    pub config: &'a Config,
+    //prefix lines and counts with the file name
+    pub show_names: bool,
+    //highlight matches and prefixes, --color having been settled
+    pub color: bool,
+}
 
-    for line in res {
-        println!("{line}");
+impl Searcher<'_> {
+    //`-` being stdin. True if the file had a selected line, or for -L none.
+    pub fn search_file(&self, file: &Path, out: &mut dyn Write) -> io::Result<bool> {
+        if file == Path::new(STDIN_PATH) {
+            return self.search_reader(io::stdin().lock(), STDIN_NAME, out);
+        }
+        let name = file.display().to_string();
+        self.search_reader(File::open(file)?, &name, out)
     }
-    Ok(())
-}
 
-pub fn search<'a>(query: &str, content: &'a str) -> Vec<&'a str> {
-    let mut res = Vec::new();
-    for line in content.lines() {
-        if line.contains(query) {
-            res.push(line.trim());
+    //--in-place: replaces the matches in `file` and writes it back, or with --dry-run prints
+    //the diff. True if there was something to replace.
+    pub fn replace_file(&self, file: &Path, out: &mut dyn Write) -> io::Result<bool> {
+        let template = self.config.replace.as_deref().unwrap_or_default();
+        //read whole, since it is rewritten whole. Not text is left alone rather than mangled,
+        //and is not an error: it is only not what --in-place is for.
+        let content = match String::from_utf8(fs::read(file)?) {
+            Ok(content) => content,
+            Err(_) => {
+                eprintln!("grep: {}: not UTF-8, left as it is", file.display());
+                return Ok(false);
+            }
+        };
+        let lines = replace::replace_lines(self.matcher, template, &content);
+        if lines.iter().all(|(_, replaced)| replaced.is_none()) {
+            return Ok(false);
+        }
+        if self.config.dry_run {
+            replace::write_diff(out, &file.display().to_string(), &lines)?;
+        } else {
+            replace::write_atomically(file, &replace::joined(&lines))?;
         }
+        Ok(true)
     }
 
-    res
+    //searches line by line, so memory use does not grow with the size of the input
+    pub fn search_reader<R: Read>(&self, reader: R, name: &str, out: &mut dyn Write)
+        -> io::Result<bool> {
+        let config = self.config;
+        let mut lines = LineReader::new(reader);
+        let mut printer = Printer::new(self, name);
+        let mut line_number = 0;
+        let mut count = 0;
+        loop {
+            let offset = lines.offset();
+            let line = match lines.next_line()? {
+                Some(line) => line,
+                None => break,
+            };
+            line_number += 1;
+            if self.matcher.is_match(&line) == config.invert_match {
+                if config.output == Output::Lines {
+                    printer.skip_line(out, line_number, &line)?;
+                }
+                continue;
+            }
+            count += 1;
+            match config.output {
+                Output::Lines => {
+                    //with -v the line has nothing to show
+                    let ranges = if config.invert_match {
+                        Vec::new()
+                    } else {
+                        self.matcher.find_all(&line)
+                    };
+                    printer.print_match(out, &Match { line_number, line: &line, ranges }, offset)?
+                }
+                Output::Count => (),
+                //the first selected line settles it
+                Output::FilesWithMatches | Output::FilesWithoutMatch => break,
+            }
+        }
+        match config.output {
+            Output::Count => {
+                if self.show_names {
+                    write!(out, "{}:", name)?;
+                }
+                writeln!(out, "{}", count)?;
+            }
+            Output::FilesWithMatches if count > 0 => writeln!(out, "{}", name)?,
+            Output::FilesWithoutMatch if count == 0 => writeln!(out, "{}", name)?,
+            _ => (),
+        }
+        if config.output == Output::FilesWithoutMatch {
+            Ok(count == 0)
+        } else {
+            Ok(count > 0)
+        }
+    }
 }
 
-pub fn case_insensitive_search<'a>(query: &str, content: &'a str) -> Vec<&'a str> {
-    let query = query.to_lowercase();
+pub fn matching_lines<'a>(matcher: &dyn Matcher, content: &'a str) -> Vec<Match<'a>> {
     let mut res = Vec::new();
-    for line in content.lines() {
-        if line.to_lowercase().contains(&query) {
-            res.push(line.trim());
+    for (i, line) in content.lines().enumerate() {
+        if matcher.is_match(line) {
+            res.push(Match { line_number: i + 1, line, ranges: matcher.find_all(line) });
         }
     }
 
     res
 }
 
+pub fn search<'a>(query: &str, content: &'a str) -> Vec<Match<'a>> {
+    matching_lines(&FixedMatcher::new(query, false), content)
+}
+
+pub fn case_insensitive_search<'a>(query: &str, content: &'a str) -> Vec<Match<'a>> {
+    matching_lines(&FixedMatcher::new(query, true), content)
+}
+
 //Test
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    //a line with one match
+    fn found(line_number: usize, line: &str, range: Range<usize>) -> Match<'_> {
+        Match { line_number, line, ranges: vec![range] }
+    }
+
     #[test]
     fn one_result() {
         let query = "ductive";
@@ -73,7 +233,10 @@ mod tests {
         safe, fast, productive\n
         Pick three.";
 
-        assert_eq!(vec!["safe, fast, productive"], search(query, content))
+        assert_eq!(
+            vec![found(5, "        safe, fast, productive", 23..30)],
+            search(query, content)
+        )
     }
 
     #[test]
@@ -85,7 +248,7 @@ mod tests {
         And trouble deaf heaven with my bootless cries";
 
         assert_eq!(
-            vec!["When, in disgrace with fortune and men’s eyes,"],
+            vec![found(2, "        When, in disgrace with fortune and men’s eyes,", 39..42)],
             search(query, content)
         );
     }
@@ -99,10 +262,121 @@ mod tests {
 
         assert_eq!(
             vec![
-                "When, in disgrace with fortune and men’s eyes,",
-                "And trouble deaf heaven with my bootless cries"
+                found(1, "When, in disgrace with fortune and men’s eyes,", 31..34),
+                found(3, "        And trouble deaf heaven with my bootless cries", 8..11)
             ],
             case_insensitive_search(query, content)
         )
     }
+
+    #[test]
+    fn regex_by_default() {
+        let content = "fn main() {\n    let x = 42;\n    let y = x + 1;\n}";
+        let args: Vec<String> = ["grep", "^\\s*let [a-z] = \\d+;$", "main.rs"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        let config = Config::new(&args).unwrap();
+        let matcher = config.matcher().unwrap();
+        assert_eq!(vec![found(2, "    let x = 42;", 0..15)],
+            matching_lines(matcher.as_ref(), content));
+
+        let mut args = args;
+        args.insert(1, "-F".to_string());
+        let config = Config::new(&args).unwrap();
+        assert_eq!(config.patterns, ["^\\s*let [a-z] = \\d+;$"]);
+        assert!(matching_lines(config.matcher().unwrap().as_ref(), content).is_empty());
+        assert!(Config::new(&args[..2]).is_err());
+    }
+
+    fn searched(args: &str, show_names: bool) -> (String, bool) {
+        searched_in("one apple\ntwo pears\nthree apples\n", args, show_names)
+    }
+
+    fn searched_in(content: &str, args: &str, show_names: bool) -> (String, bool) {
+        let args: Vec<String> = std::iter::once("grep")
+            .chain(args.split_whitespace())
+            .map(String::from)
+            .collect();
+        let config = Config::new(&args).unwrap();
+        let matcher = config.matcher().unwrap();
+        let searcher = Searcher {
+            matcher: matcher.as_ref(),
+            config: &config,
+            show_names,
+            color: false,
+        };
+        let mut out = Vec::new();
+        let found = searcher.search_reader(content.as_bytes(), "fruit.txt", &mut out).unwrap();
+        (String::from_utf8(out).unwrap(), found)
+    }
+
+    #[test]
+    fn prints_what_the_flags_ask_for() {
+        assert_eq!(searched("-n apple", true),
+            ("fruit.txt:1:one apple\nfruit.txt:3:three apples\n".to_string(), true));
+        assert_eq!(searched("-vn apple", false), ("2:two pears\n".to_string(), true));
+        assert_eq!(searched("-c apple", true), ("fruit.txt:2\n".to_string(), true));
+        assert_eq!(searched("-c plum", false), ("0\n".to_string(), false));
+        assert_eq!(searched("-l pear", false), ("fruit.txt\n".to_string(), true));
+        assert_eq!(searched("-l plum", false), (String::new(), false));
+        assert_eq!(searched("-L plum", false), ("fruit.txt\n".to_string(), true));
+        assert_eq!(searched("-L pear", false), (String::new(), false));
+        assert_eq!(searched("-n -A1 two", true),
+            ("fruit.txt:2:two pears\nfruit.txt-3-three apples\n".to_string(), true));
+        assert_eq!(searched("-B1 -A1 pear", false),
+            ("one apple\ntwo pears\nthree apples\n".to_string(), true));
+        assert_eq!(searched("-C1 -c apple", false), ("2\n".to_string(), true));
+        assert_eq!(searched("--replace=[$1] (p|t)\\w* -n", false),
+            ("1:one a[p]\n2:[t] [p]\n3:[t] a[p]\n".to_string(), true));
+    }
+
+    #[test]
+    fn replaces_in_place() {
+        let dir = std::env::temp_dir().join(format!("grep-replace-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("main.rs");
+        fs::write(&file, "fn main() {\n    let x = old(1);\n}\n").unwrap();
+        let args = |extra: &str| {
+            let args = format!("grep --replace new($1) --in-place {} old\\((\\d)\\) {}",
+                extra, file.display());
+            Config::new(&args.split_whitespace().map(String::from).collect::<Vec<_>>()).unwrap()
+        };
+
+        let config = args("--dry-run");
+        let matcher = config.matcher().unwrap();
+        let searcher = Searcher { matcher: matcher.as_ref(), config: &config, show_names: false,
+            color: false };
+        let mut out = Vec::new();
+        assert!(searcher.replace_file(&file, &mut out).unwrap());
+        assert_eq!(String::from_utf8(out).unwrap(), format!(
+            "--- {0}\n+++ {0}\n@@ -1,3 +1,3 @@\n fn main() {{\n-    let x = old(1);\n\
+             +    let x = new(1);\n }}\n", file.display()));
+        assert!(fs::read_to_string(&file).unwrap().contains("old(1)"));
+
+        let config = args("");
+        let searcher = Searcher { config: &config, ..searcher };
+        assert!(searcher.replace_file(&file, &mut Vec::new()).unwrap());
+        assert_eq!(fs::read_to_string(&file).unwrap(), "fn main() {\n    let x = new(1);\n}\n");
+        assert!(!searcher.replace_file(&file, &mut Vec::new()).unwrap());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        //a file that is not text is skipped, not an error
+        let binary = dir.join("data.bin");
+        fs::write(&binary, b"old(1)\xff\n").unwrap();
+        assert!(!searcher.replace_file(&binary, &mut Vec::new()).unwrap());
+        assert_eq!(fs::read(&binary).unwrap(), b"old(1)\xff\n");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_leading_whitespace_unless_trimmed() {
+        let content = "fn main() {\n    let x = 1;\n\tlet y = 2;  \r\n}\n";
+        assert_eq!(searched_in(content, "-n let", false),
+            ("2:    let x = 1;\n3:\tlet y = 2;  \n".to_string(), true));
+        assert_eq!(searched_in(content, "--trim -C1 y", false),
+            ("let x = 1;\nlet y = 2;\n}\n".to_string(), true));
+        assert_eq!(searched_in(content, "--column x", false),
+            ("2:9:    let x = 1;\n".to_string(), true));
+    }
 }
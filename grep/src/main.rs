@@ -1,23 +1,31 @@
 /*
 grep: globally search a regular expression and print
-takes 2 arguments:
-1. filename
-2. string pattern
+see `grep --help`, or USAGE in config.rs, for the options
 */
 
 use std::env;
+use std::io::{self, Write};
 use std::process;
-use grep::{Config, run};
+use grep::{ArgsError, Config, run};
 fn main() {
     let args: Vec<String> = env::args().collect();
     let config = Config::new(&args).unwrap_or_else(|err|{
-        println!("Problem parsing arguments: {}", err);
-        process::exit(1);
+        if let ArgsError::Help = err {
+            //the usage is often piped into a pager that may quit early
+            let _ = writeln!(io::stdout(), "{}", err);
+            process::exit(0);
+        }
+        eprintln!("grep: {}", err);
+        process::exit(2);
     });
 
-    run(config).unwrap_or_else(|err|{
-        println!("Problem when reading file: {:?}", err.to_string());
-        process::exit(1);
-    });
+    match run(config) {
+        Ok(summary) if summary.errors > 0 => process::exit(2),
+        Ok(summary) if summary.matched => (),
+        Ok(_) => process::exit(1),
+        Err(err) => {
+            eprintln!("grep: {}", err);
+            process::exit(2);
+        }
+    }
 }
-
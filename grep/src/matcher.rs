@@ -0,0 +1,235 @@
+use crate::casefold::full_fold;
+use crate::regex::Regex;
+use std::ops::Range;
+
+//finds what the user searches for in a line, shared by the threads searching files
+pub trait Matcher: Send + Sync {
+    //byte range of the first match starting at or after byte `start`
+    fn find_at(&self, line: &str, start: usize) -> Option<(usize, usize)>;
+
+    fn is_match(&self, line: &str) -> bool {
+        self.find_at(line, 0).is_some()
+    }
+
+    //the match at or after `start` and the byte ranges of its groups, the whole match being
+    //group 0. Only regular expressions have more.
+    fn captures_at(&self, line: &str, start: usize) -> Option<Vec<Option<(usize, usize)>>> {
+        self.find_at(line, start).map(|found| vec![Some(found)])
+    }
+
+    //the byte ranges of the matches in `line` one after the other, leaving out empty ones
+    fn find_all(&self, line: &str) -> Vec<Range<usize>> {
+        let mut ranges = Vec::new();
+        let mut start = 0;
+        while let Some((match_start, match_end)) = self.find_at(line, start) {
+            if match_end > match_start {
+                ranges.push(match_start..match_end);
+                start = match_end;
+            } else {
+                //the next search starts a character later
+                match line[match_end..].chars().next() {
+                    Some(c) => start = match_end + c.len_utf8(),
+                    None => break,
+                }
+            }
+        }
+        ranges
+    }
+}
+
+//the pattern taken literally (-F)
+pub struct FixedMatcher {
+    needle: String,
+    case_insensitive: bool,
+}
+
+impl FixedMatcher {
+    pub fn new(needle: &str, case_insensitive: bool) -> Self {
+        FixedMatcher {
+            needle: needle.to_string(),
+            case_insensitive,
+        }
+    }
+
+    //the end of the needle if `text` starts with it, comparing case folded characters. A
+    //character of `text` folding to several, like `ß` to `ss`, has to match all of them.
+    fn prefix_end(&self, text: &str) -> Option<usize> {
+        let mut needle = self.needle.chars().flat_map(full_fold).peekable();
+        let mut end = 0;
+        for c in text.chars() {
+            if needle.peek().is_none() {
+                break;
+            }
+            for folded in full_fold(c) {
+                if needle.next() != Some(folded) {
+                    return None;
+                }
+            }
+            end += c.len_utf8();
+        }
+        needle.peek().is_none().then_some(end)
+    }
+}
+
+impl Matcher for FixedMatcher {
+    fn find_at(&self, line: &str, start: usize) -> Option<(usize, usize)> {
+        if !self.case_insensitive {
+            return line[start..]
+                .find(&self.needle)
+                .map(|found| (start + found, start + found + self.needle.len()));
+        }
+        line[start..]
+            .char_indices()
+            .map(|(i, _)| start + i)
+            .chain(std::iter::once(line.len()))
+            .find_map(|at| self.prefix_end(&line[at..]).map(|end| (at, at + end)))
+    }
+}
+
+impl Matcher for Regex {
+    fn find_at(&self, line: &str, start: usize) -> Option<(usize, usize)> {
+        Regex::find_at(self, line, start)
+    }
+
+    fn is_match(&self, line: &str) -> bool {
+        Regex::is_match(self, line)
+    }
+
+    fn captures_at(&self, line: &str, start: usize) -> Option<Vec<Option<(usize, usize)>>> {
+        Regex::captures_at(self, line, start)
+    }
+}
+
+//-w: only matches with no letter, digit or `_` right before or after them
+pub struct WordMatcher {
+    inner: Box<dyn Matcher>,
+}
+
+impl WordMatcher {
+    pub fn new(inner: Box<dyn Matcher>) -> Self {
+        WordMatcher { inner }
+    }
+}
+
+impl Matcher for WordMatcher {
+    fn find_at(&self, line: &str, start: usize) -> Option<(usize, usize)> {
+        let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+        let mut from = start;
+        while from <= line.len() {
+            let (match_start, match_end) = self.inner.find_at(line, from)?;
+            let before = line[..match_start].chars().next_back();
+            let after = line[match_end..].chars().next();
+            if !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char) {
+                return Some((match_start, match_end));
+            }
+            //try again from the next character
+            from = match_start + line[match_start..].chars().next().map_or(1, char::len_utf8);
+        }
+        None
+    }
+
+    fn captures_at(&self, line: &str, start: usize) -> Option<Vec<Option<(usize, usize)>>> {
+        //the inner matcher finds the same match again from where it starts
+        let (match_start, _) = self.find_at(line, start)?;
+        self.inner.captures_at(line, match_start)
+    }
+}
+
+//several patterns: the leftmost match of any of them, the longest if they start together
+pub struct MultiMatcher {
+    matchers: Vec<Box<dyn Matcher>>,
+}
+
+impl MultiMatcher {
+    pub fn new(matchers: Vec<Box<dyn Matcher>>) -> Self {
+        MultiMatcher { matchers }
+    }
+}
+
+impl Matcher for MultiMatcher {
+    fn find_at(&self, line: &str, start: usize) -> Option<(usize, usize)> {
+        self.matchers
+            .iter()
+            .filter_map(|matcher| matcher.find_at(line, start))
+            .min_by_key(|(match_start, match_end)| (*match_start, usize::MAX - match_end))
+    }
+
+    fn is_match(&self, line: &str) -> bool {
+        self.matchers.iter().any(|matcher| matcher.is_match(line))
+    }
+
+    fn captures_at(&self, line: &str, start: usize) -> Option<Vec<Option<(usize, usize)>>> {
+        self.matchers
+            .iter()
+            .filter_map(|matcher| matcher.captures_at(line, start))
+            .min_by_key(|groups| match groups[0] {
+                Some((match_start, match_end)) => (match_start, usize::MAX - match_end),
+                None => (usize::MAX, usize::MAX),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_strings_are_not_patterns() {
+        let matcher = FixedMatcher::new("a.c", false);
+        assert_eq!(matcher.find_at("abc a.c", 0), Some((4, 7)));
+        assert!(!matcher.is_match("abc"));
+
+        let matcher = FixedMatcher::new("ÉTÉ", true);
+        assert_eq!(matcher.find_at("l'été, été", 4), Some((9, 14)));
+
+        //offsets are in the text as it is, whatever folding did to its length
+        let matcher = FixedMatcher::new("STRASSE", true);
+        assert_eq!(matcher.find_at("die Straße", 0), Some((4, 11)));
+        assert_eq!(
+            FixedMatcher::new("straße", true).find_at("STRASSE", 0),
+            Some((0, 7))
+        );
+        assert!(!FixedMatcher::new("se", true).is_match("Straße"));
+        let matcher = FixedMatcher::new("ΣΟΦΟΣ", true);
+        assert_eq!(matcher.find_all("σοφος σοφοσ"), [0..10, 11..21]);
+        assert!(!FixedMatcher::new("i", true).is_match("ıİ"));
+        assert!(!FixedMatcher::new("ı", true).is_match("Ii"));
+        assert!(FixedMatcher::new("i\u{307}", true).is_match("İ"));
+        assert!(FixedMatcher::new("", true).is_match(""));
+        assert!(FixedMatcher::new("", false).find_all("ab").is_empty());
+        assert_eq!(
+            Regex::new("a*", false).unwrap().find_all("baab a"),
+            [1..3, 5..6]
+        );
+    }
+
+    #[test]
+    fn words_and_several_patterns() {
+        let word = WordMatcher::new(Box::new(FixedMatcher::new("cat", false)));
+        assert_eq!(word.find_at("concat cat_ cat.", 0), Some((12, 15)));
+        assert!(!word.is_match("cats"));
+
+        let patterns = MultiMatcher::new(vec![
+            Box::new(FixedMatcher::new("dog", false)),
+            Box::new(FixedMatcher::new("do", false)),
+            Box::new(Regex::new("c.t", false).unwrap()),
+        ]);
+        assert_eq!(patterns.find_at("a dog and a cat", 0), Some((2, 5)));
+        assert_eq!(patterns.find_at("a dog and a cat", 3), Some((12, 15)));
+        let patterns = MultiMatcher::new(vec![
+            Box::new(FixedMatcher::new("cat", false)),
+            Box::new(WordMatcher::new(Box::new(
+                Regex::new("c(.)t", false).unwrap(),
+            ))),
+        ]);
+        assert_eq!(
+            patterns.captures_at("concat cut", 0),
+            Some(vec![Some((3, 6))])
+        );
+        assert_eq!(
+            patterns.captures_at("concat cut", 4),
+            Some(vec![Some((7, 10)), Some((8, 9))])
+        );
+        assert!(!MultiMatcher::new(Vec::new()).is_match("anything"));
+    }
+}
@@ -0,0 +1,204 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::thread;
+
+//how much of a file's output a worker collects before handing it on
+const CHUNK_SIZE: usize = 64 * 1024;
+//chunks a worker may have waiting before it stops until its file is written out
+const CHUNKS_AHEAD: usize = 4;
+
+//what a worker hands on while searching a file: its output a chunk at a time, then how it went
+enum Piece {
+    Output(Vec<u8>),
+    Done(io::Result<()>),
+}
+
+//runs `search` over `files` on up to `threads` threads, writing what it finds to `out`. Run on
+//one thread it writes to `out` directly. Otherwise one file at a time is written out, the others
+//wait with at most a few chunks of output each, so the output of different files is never
+//interleaved and memory stays bounded. Files are written in the order they were started, or in
+//the order of `files` when `sorted`.
+//Failed searches go to `on_error`, whose error stops the workers and is returned.
+pub fn search_files<S, E>(
+    files: &[PathBuf],
+    threads: usize,
+    sorted: bool,
+    search: S,
+    out: &mut dyn Write,
+    mut on_error: E,
+) -> io::Result<()>
+where
+    S: Fn(&Path, &mut dyn Write) -> io::Result<()> + Sync,
+    E: FnMut(&Path, io::Error) -> io::Result<()>,
+{
+    let threads = threads.clamp(1, files.len().max(1));
+    if threads == 1 {
+        for file in files {
+            if let Err(err) = search(file, out) {
+                on_error(file, err)?;
+            }
+        }
+        return Ok(());
+    }
+
+    let next_file = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false);
+    //each file started, with where its output comes from
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            let sender = sender.clone();
+            let (next_file, stop, search) = (&next_file, &stop, &search);
+            scope.spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    let index = next_file.fetch_add(1, Ordering::Relaxed);
+                    let file = match files.get(index) {
+                        Some(file) => file,
+                        None => break,
+                    };
+                    let (pieces, piece_receiver) = mpsc::sync_channel(CHUNKS_AHEAD);
+                    if sender.send((index, piece_receiver)).is_err() {
+                        break;
+                    }
+                    let mut output = ChunkWriter {
+                        chunk: Vec::new(),
+                        pieces: &pieces,
+                    };
+                    let result = search(file, &mut output).and_then(|()| output.flush());
+                    if pieces.send(Piece::Done(result)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        let mut emit = |index: usize, pieces: Receiver<Piece>| {
+            for piece in pieces {
+                match piece {
+                    Piece::Output(chunk) => out.write_all(&chunk)?,
+                    Piece::Done(Ok(())) => return Ok(()),
+                    Piece::Done(Err(err)) => return on_error(&files[index], err),
+                }
+            }
+            Ok(())
+        };
+        //files started before the ones sorted ahead of them
+        let mut waiting = BTreeMap::new();
+        let mut next_to_emit = 0;
+        let result = receiver.iter().try_for_each(|(index, pieces)| {
+            if !sorted {
+                return emit(index, pieces);
+            }
+            waiting.insert(index, pieces);
+            while let Some(pieces) = waiting.remove(&next_to_emit) {
+                emit(next_to_emit, pieces)?;
+                next_to_emit += 1;
+            }
+            Ok(())
+        });
+        if result.is_err() {
+            //workers waiting to hand on output give up once nobody is there to take it
+            stop.store(true, Ordering::Relaxed);
+            drop(waiting);
+            drop(receiver);
+        }
+        result
+    })
+}
+
+//collects a worker's output into chunks and hands each on once it is big enough
+struct ChunkWriter<'a> {
+    chunk: Vec<u8>,
+    pieces: &'a SyncSender<Piece>,
+}
+
+impl Write for ChunkWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.chunk.extend_from_slice(buf);
+        if self.chunk.len() >= CHUNK_SIZE {
+            self.flush()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.chunk.is_empty() {
+            return Ok(());
+        }
+        let chunk = std::mem::take(&mut self.chunk);
+        self.pieces
+            .send(Piece::Output(chunk))
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn keeps_files_whole_and_sorts_when_asked() {
+        let files: Vec<PathBuf> = (0..20).map(|i| PathBuf::from(i.to_string())).collect();
+        //later files finish first
+        let search = |file: &Path, out: &mut dyn Write| {
+            let number: u64 = file.to_string_lossy().parse().unwrap();
+            thread::sleep(Duration::from_millis(20 - number));
+            writeln!(out, "{}a", number)?;
+            writeln!(out, "{}b", number)
+        };
+        let no_errors = |_: &Path, err| Err(err);
+        let mut output = Vec::new();
+        search_files(&files, 4, true, search, &mut output, no_errors).unwrap();
+        let expected: String = (0..20).map(|i| format!("{}a\n{}b\n", i, i)).collect();
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+
+        let mut output = Vec::new();
+        search_files(&files, 4, false, search, &mut output, no_errors).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 40);
+        for pair in lines.chunks(2) {
+            assert_eq!(pair[0].replace('a', "b"), pair[1]);
+        }
+
+        let mut errors = 0;
+        let failing = |_: &Path, _: &mut dyn Write| Err(io::Error::other("unreadable"));
+        let result = search_files(&files, 4, false, failing, &mut Vec::new(), |_, err| {
+            errors += 1;
+            Err(err)
+        });
+        assert!(result.is_err());
+        assert_eq!(errors, 1);
+    }
+
+    #[test]
+    fn streams_big_outputs_a_file_at_a_time() {
+        let files: Vec<PathBuf> = (0..8).map(|i| PathBuf::from(i.to_string())).collect();
+        //several chunks per file, more than a worker may keep waiting
+        let lines = CHUNK_SIZE * (CHUNKS_AHEAD + 2) / 8;
+        let search = |file: &Path, out: &mut dyn Write| {
+            for _ in 0..lines {
+                writeln!(out, "{:>7}", file.display())?;
+            }
+            Ok(())
+        };
+        for sorted in [true, false] {
+            let mut output = Vec::new();
+            search_files(&files, 4, sorted, search, &mut output, |_, err| Err(err)).unwrap();
+            let output = String::from_utf8(output).unwrap();
+            let mut seen: Vec<&str> = output.lines().collect();
+            assert_eq!(seen.len(), lines * files.len());
+            seen.dedup();
+            assert_eq!(seen.len(), files.len());
+            if sorted {
+                assert_eq!(seen[0].trim(), "0");
+                assert_eq!(seen[7].trim(), "7");
+            }
+        }
+    }
+}
@@ -0,0 +1,297 @@
+use crate::replace;
+use crate::{Match, Searcher};
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::ops::Range;
+
+//printed between groups of lines that are not next to each other, when there is context
+const GROUP_SEPARATOR: &str = "--";
+
+//SGR sequences, the same colors GNU grep uses
+const MATCH_COLOR: &str = "\x1b[1;31m";
+const PATH_COLOR: &str = "\x1b[35m";
+const NUMBER_COLOR: &str = "\x1b[32m";
+const SEPARATOR_COLOR: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
+
+//prints the matching lines of one file and the context around them. Selected lines are marked
+//`name:number:column:`, context lines `name-number-`. Context windows that overlap or touch are
+//printed as one group, so no line is printed twice. With --json each selected line is one object
+//and there is no context.
+pub struct Printer<'a> {
+    searcher: &'a Searcher<'a>,
+    name: &'a str,
+    //lines since the last one printed, in case a match comes within -B of them
+    before: VecDeque<(usize, String)>,
+    //how many of the next lines are still after context
+    after_left: usize,
+    last_printed: Option<usize>,
+}
+
+impl<'a> Printer<'a> {
+    pub fn new(searcher: &'a Searcher<'a>, name: &'a str) -> Self {
+        Printer {
+            searcher,
+            name,
+            before: VecDeque::new(),
+            after_left: 0,
+            last_printed: None,
+        }
+    }
+
+    //`offset` is where the line starts in the file, in bytes
+    pub fn print_match(
+        &mut self,
+        out: &mut dyn Write,
+        found: &Match,
+        offset: u64,
+    ) -> io::Result<()> {
+        let config = self.searcher.config;
+        if config.json {
+            return self.print_json(out, found, offset);
+        }
+        while let Some((line_number, line)) = self.before.pop_front() {
+            self.print_line(out, line_number, &line, false, &[], None)?;
+        }
+        match &config.replace {
+            Some(template) => {
+                let (ranges, replacements): (Vec<_>, Vec<_>) =
+                    replace::replacements(self.searcher.matcher, template, found.line)
+                        .into_iter()
+                        .unzip();
+                let line = found.line;
+                self.print_line(
+                    out,
+                    found.line_number,
+                    line,
+                    true,
+                    &ranges,
+                    Some(&replacements),
+                )?
+            }
+            None => self.print_line(
+                out,
+                found.line_number,
+                found.line,
+                true,
+                &found.ranges,
+                None,
+            )?,
+        }
+        self.after_left = config.after_context;
+        Ok(())
+    }
+
+    //a line that was not selected, printed if it is context
+    pub fn skip_line(
+        &mut self,
+        out: &mut dyn Write,
+        line_number: usize,
+        line: &str,
+    ) -> io::Result<()> {
+        let config = self.searcher.config;
+        if config.json {
+            return Ok(());
+        }
+        if self.after_left > 0 {
+            self.after_left -= 1;
+            return self.print_line(out, line_number, line, false, &[], None);
+        }
+        if config.before_context > 0 {
+            if self.before.len() == config.before_context {
+                self.before.pop_front();
+            }
+            self.before.push_back((line_number, line.to_string()));
+        }
+        Ok(())
+    }
+
+    //context lines have no ranges and `-` rather than `:` after the prefixes. With --replace
+    //each range is printed as its replacement.
+    fn print_line(
+        &mut self,
+        out: &mut dyn Write,
+        line_number: usize,
+        line: &str,
+        selected: bool,
+        ranges: &[Range<usize>],
+        replacements: Option<&[String]>,
+    ) -> io::Result<()> {
+        let config = self.searcher.config;
+        let has_context = config.before_context > 0 || config.after_context > 0;
+        match self.last_printed {
+            Some(last) if has_context && line_number > last + 1 => {
+                self.write_colored(out, SEPARATOR_COLOR, GROUP_SEPARATOR)?;
+                writeln!(out)?;
+            }
+            _ => (),
+        }
+        self.last_printed = Some(line_number);
+        let separator = if selected { ":" } else { "-" };
+        if self.searcher.show_names {
+            self.write_colored(out, PATH_COLOR, self.name)?;
+            self.write_colored(out, SEPARATOR_COLOR, separator)?;
+        }
+        if config.line_number {
+            self.write_colored(out, NUMBER_COLOR, &line_number.to_string())?;
+            self.write_colored(out, SEPARATOR_COLOR, separator)?;
+        }
+        if let Some(range) = ranges.first().filter(|_| selected && config.column) {
+            let column = range.start + 1;
+            self.write_colored(out, NUMBER_COLOR, &column.to_string())?;
+            self.write_colored(out, SEPARATOR_COLOR, separator)?;
+        }
+
+        //the ranges are in the whole line, and cut down to what is left of it with --trim
+        let text = if config.trim { line.trim() } else { line };
+        let lead = line.len() - line.trim_start().len();
+        let lead = if config.trim { lead } else { 0 };
+        let mut printed = 0;
+        for (i, range) in ranges.iter().enumerate() {
+            let start = range.start.clamp(lead, lead + text.len()) - lead;
+            let end = range.end.clamp(lead, lead + text.len()) - lead;
+            let shown = match replacements {
+                Some(replacements) => replacements[i].as_str(),
+                None => &text[start..end],
+            };
+            write!(out, "{}", &text[printed..start])?;
+            if !shown.is_empty() {
+                self.write_colored(out, MATCH_COLOR, shown)?;
+            }
+            printed = end;
+        }
+        writeln!(out, "{}", &text[printed..])
+    }
+
+    fn write_colored(&self, out: &mut dyn Write, color: &str, text: &str) -> io::Result<()> {
+        if self.searcher.color {
+            write!(out, "{}{}{}", color, text, RESET)
+        } else {
+            out.write_all(text.as_bytes())
+        }
+    }
+
+    fn print_json(&self, out: &mut dyn Write, found: &Match, offset: u64) -> io::Result<()> {
+        write!(out, "{{\"path\":")?;
+        write_json_string(out, self.name)?;
+        write!(
+            out,
+            ",\"line_number\":{},\"absolute_offset\":{},\"text\":",
+            found.line_number, offset
+        )?;
+        write_json_string(out, found.line)?;
+        write!(out, ",\"submatches\":[")?;
+        for (i, range) in found.ranges.iter().enumerate() {
+            if i > 0 {
+                write!(out, ",")?;
+            }
+            write!(out, "{{\"match\":")?;
+            write_json_string(out, &found.line[range.clone()])?;
+            write!(out, ",\"start\":{},\"end\":{}}}", range.start, range.end)?;
+        }
+        writeln!(out, "]}}")
+    }
+}
+
+fn write_json_string(out: &mut dyn Write, text: &str) -> io::Result<()> {
+    write!(out, "\"")?;
+    for c in text.chars() {
+        match c {
+            '"' => write!(out, "\\\"")?,
+            '\\' => write!(out, "\\\\")?,
+            '\n' => write!(out, "\\n")?,
+            '\r' => write!(out, "\\r")?,
+            '\t' => write!(out, "\\t")?,
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32)?,
+            c => write!(out, "{}", c)?,
+        }
+    }
+    write!(out, "\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+
+    fn printed(lines: &str, config: &Config, color: bool) -> String {
+        let matcher = config.matcher().unwrap();
+        let searcher = Searcher {
+            matcher: matcher.as_ref(),
+            config,
+            show_names: false,
+            color,
+        };
+        let mut printer = Printer::new(&searcher, "log");
+        let mut out = Vec::new();
+        let mut offset = 0;
+        for (i, line) in lines.lines().enumerate() {
+            if matcher.is_match(line) {
+                let found = Match {
+                    line_number: i + 1,
+                    line,
+                    ranges: matcher.find_all(line),
+                };
+                printer.print_match(&mut out, &found, offset).unwrap();
+            } else {
+                printer.skip_line(&mut out, i + 1, line).unwrap();
+            }
+            offset += line.len() as u64 + 1;
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    fn config(pattern: &str, before: usize, after: usize) -> Config {
+        Config {
+            patterns: vec![pattern.to_string()],
+            line_number: true,
+            before_context: before,
+            after_context: after,
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn merges_context_and_separates_groups() {
+        let lines = "a\nb\nERR\nc\nd\ne\nf\nERR\ng\nERR\nh\ni";
+        let printed = |before, after| printed(lines, &config("ERR", before, after), false);
+        assert_eq!(printed(0, 0), "3:ERR\n8:ERR\n10:ERR\n");
+        assert_eq!(
+            printed(1, 1),
+            "2-b\n3:ERR\n4-c\n--\n7-f\n8:ERR\n9-g\n10:ERR\n11-h\n"
+        );
+        //the windows of the first two matches touch, so they are one group
+        assert_eq!(
+            printed(2, 2),
+            "1-a\n2-b\n3:ERR\n4-c\n5-d\n6-e\n7-f\n8:ERR\n9-g\n10:ERR\n11-h\n12-i\n"
+        );
+        assert_eq!(printed(0, 1), "3:ERR\n4-c\n--\n8:ERR\n9-g\n10:ERR\n11-h\n");
+        assert_eq!(printed(3, 3).lines().count(), 12);
+    }
+
+    #[test]
+    fn highlights_columns_and_json() {
+        let lines = "  a cat, a cat\nno dog\n";
+        let mut config = config("cat", 0, 0);
+        config.column = true;
+        assert_eq!(printed(lines, &config, false), "1:5:  a cat, a cat\n");
+        config.trim = true;
+        assert_eq!(
+            printed(lines, &config, true),
+            "\x1b[32m1\x1b[0m\x1b[36m:\x1b[0m\x1b[32m5\x1b[0m\x1b[36m:\x1b[0m\
+             a \x1b[1;31mcat\x1b[0m, a \x1b[1;31mcat\x1b[0m\n"
+        );
+
+        config.json = true;
+        config.patterns = vec!["o|\"".to_string()];
+        assert_eq!(
+            printed("x\n\"no\"\tdog\n", &config, false),
+            "{\"path\":\"log\",\"line_number\":2,\"absolute_offset\":2,\
+             \"text\":\"\\\"no\\\"\\tdog\",\"submatches\":[\
+             {\"match\":\"\\\"\",\"start\":0,\"end\":1},\
+             {\"match\":\"o\",\"start\":2,\"end\":3},\
+             {\"match\":\"\\\"\",\"start\":3,\"end\":4},\
+             {\"match\":\"o\",\"start\":6,\"end\":7}]}\n"
+        );
+    }
+}
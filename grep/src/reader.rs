@@ -0,0 +1,94 @@
+use std::borrow::Cow;
+use std::io::{self, BufRead, BufReader, Read};
+
+//past this a line is cut, so a huge file without newlines cannot take all the memory
+const MAX_LINE_BYTES: usize = 16 * 1024 * 1024;
+
+//reads lines one at a time into the same buffer, whatever the size of the input
+pub struct LineReader<R> {
+    reader: BufReader<R>,
+    line: Vec<u8>,
+    max_line_bytes: usize,
+    //bytes consumed so far
+    consumed: u64,
+}
+
+impl<R: Read> LineReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_limit(reader, MAX_LINE_BYTES)
+    }
+
+    fn with_limit(reader: R, max_line_bytes: usize) -> Self {
+        LineReader {
+            reader: BufReader::new(reader),
+            line: Vec::new(),
+            max_line_bytes,
+            consumed: 0,
+        }
+    }
+
+    //the byte offset in the input of the next line
+    pub fn offset(&self) -> u64 {
+        self.consumed
+    }
+
+    //the next line without its `\n` or `\r\n`, invalid UTF-8 replaced by U+FFFD.
+    //What goes past the length limit is skipped.
+    pub fn next_line(&mut self) -> io::Result<Option<Cow<'_, str>>> {
+        self.line.clear();
+        let mut read_any = false;
+        loop {
+            let available = match self.reader.fill_buf() {
+                Ok(available) => available,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            };
+            if available.is_empty() {
+                break;
+            }
+            read_any = true;
+            let (chunk, used, line_ended) = match available.iter().position(|b| *b == b'\n') {
+                Some(newline) => (&available[..newline], newline + 1, true),
+                None => (available, available.len(), false),
+            };
+            let room = self.max_line_bytes.saturating_sub(self.line.len());
+            self.line.extend_from_slice(&chunk[..chunk.len().min(room)]);
+            self.reader.consume(used);
+            self.consumed += used as u64;
+            if line_ended {
+                break;
+            }
+        }
+        if !read_any {
+            return Ok(None);
+        }
+        if self.line.last() == Some(&b'\r') {
+            self.line.pop();
+        }
+        Ok(Some(String::from_utf8_lossy(&self.line)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_lines_lossily_and_cuts_long_ones() {
+        let input: &[u8] = b"first\r\nbad \xff byte\n\nthis one is too long\nlast";
+        let mut reader = LineReader::with_limit(input, 11);
+        let mut lines = Vec::new();
+        let mut offsets = Vec::new();
+        offsets.push(reader.offset());
+        while let Some(line) = reader.next_line().unwrap() {
+            lines.push(line.into_owned());
+            offsets.push(reader.offset());
+        }
+        assert_eq!(
+            lines,
+            ["first", "bad \u{fffd} byte", "", "this one is", "last"]
+        );
+        assert_eq!(offsets, [0, 7, 18, 19, 40, 44]);
+        assert!(LineReader::new(&b""[..]).next_line().unwrap().is_none());
+    }
+}
@@ -0,0 +1,954 @@
+use crate::casefold;
+use std::error::Error as StdError;
+use std::fmt;
+
+//`a{1000}` is copied out that many times when compiled, more is surely a mistake
+const MAX_REPEAT: u32 = 1000;
+//counts inside counts multiply, so the compiled program as a whole is limited too
+const MAX_PROGRAM_SIZE: usize = 250_000;
+
+//a pattern that could not be parsed, `position` counting characters from 0
+#[derive(Debug, PartialEq)]
+pub struct Error {
+    pub message: String,
+    pub position: usize,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl StdError for Error {}
+
+//a compiled regular expression: literals, `.`, [classes], \d \w \s and their negations,
+//^ $ \b \B, alternation, (capturing groups) and (?:groups), * + ? {n,m} and their lazy variants.
+//Matching runs all alternatives side by side (a Pike VM), so it takes time linear in the
+//line whatever the pattern, and picks the leftmost match preferring earlier alternatives.
+#[derive(Debug)]
+pub struct Regex {
+    program: Vec<Inst>,
+    case_insensitive: bool,
+    //capturing groups, numbered from 1 in the order they open
+    groups: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Empty,
+    Char(char),
+    Any,
+    Class(Class),
+    LineStart,
+    LineEnd,
+    WordBoundary {
+        negated: bool,
+    },
+    Concat(Vec<Node>),
+    Alternate(Vec<Node>),
+    Group {
+        index: usize,
+        node: Box<Node>,
+    },
+    Repeat {
+        node: Box<Node>,
+        min: u32,
+        max: Option<u32>,
+        greedy: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Perl {
+    Digit,
+    Word,
+    Space,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Class {
+    ranges: Vec<(char, char)>,
+    //\d, \w, \s inside the brackets, true for the negated \D, \W, \S
+    perl: Vec<(Perl, bool)>,
+    negated: bool,
+}
+
+#[derive(Debug)]
+enum Inst {
+    Char(char),
+    Any,
+    Class(Class),
+    LineStart,
+    LineEnd,
+    WordBoundary { negated: bool },
+    //try the first, then the second
+    Split(usize, usize),
+    Jump(usize),
+    //where group `slot / 2` starts, or ends for an odd slot
+    Save(usize),
+    Match,
+}
+
+//what an instruction that consumes nothing leads to at a position
+enum Step {
+    Consume,
+    To(usize),
+    Split(usize, usize),
+    Fail,
+}
+
+impl Regex {
+    pub fn new(pattern: &str, case_insensitive: bool) -> Result<Self, Error> {
+        let mut parser = Parser::new(pattern);
+        let node = parser.parse()?;
+        if program_size(&node) > MAX_PROGRAM_SIZE {
+            return Err(Error {
+                message: "pattern too large".to_string(),
+                position: 0,
+            });
+        }
+        let mut program = Vec::new();
+        compile(&node, &mut program);
+        program.push(Inst::Match);
+        Ok(Self {
+            program,
+            case_insensitive,
+            groups: parser.groups,
+        })
+    }
+
+    pub fn groups(&self) -> usize {
+        self.groups
+    }
+
+    //byte range of the first match starting at or after byte `start`
+    pub fn find_at(&self, text: &str, start: usize) -> Option<(usize, usize)> {
+        let mut current = Threads::new(self.program.len());
+        let mut next = Threads::new(self.program.len());
+        let mut found = None;
+        let mut at = start;
+        loop {
+            let previous = text[..at].chars().next_back();
+            let c = text[at..].chars().next();
+            //a later start only matters while nothing earlier has matched
+            if found.is_none() {
+                self.add_thread(&mut current, 0, at, (previous, c));
+            }
+            if current.is_empty() && found.is_some() {
+                break;
+            }
+            let after = c.map(|c| (Some(c), text[at + c.len_utf8()..].chars().next()));
+            for &(pc, thread_start) in &current.list {
+                match &self.program[pc] {
+                    Inst::Match => {
+                        found = Some((thread_start, at));
+                        //threads after this one have lower priority
+                        break;
+                    }
+                    inst => {
+                        if let (Some(c), Some(after)) = (c, after) {
+                            if self.consumes(inst, c) {
+                                self.add_thread(&mut next, pc + 1, thread_start, after);
+                            }
+                        }
+                    }
+                }
+            }
+            std::mem::swap(&mut current, &mut next);
+            next.clear();
+            match c {
+                Some(c) => at += c.len_utf8(),
+                None => break,
+            }
+        }
+        found
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.find_at(text, 0).is_some()
+    }
+
+    //the first match starting at or after byte `start` as `find_at` finds it, and the byte
+    //ranges of its groups, the whole match being group 0. A group that took no part is None.
+    pub fn captures_at(&self, text: &str, start: usize) -> Option<Vec<Option<(usize, usize)>>> {
+        //the match found, only its start matters: from there the threads and their priorities
+        //are the same, so following them again with slots gives the same match
+        let (match_start, _) = self.find_at(text, start)?;
+        let slots = vec![None; 2 * (self.groups + 1)];
+        let mut current = Vec::new();
+        let mut seen = vec![false; self.program.len()];
+        let mut at = match_start;
+        let previous = text[..at].chars().next_back();
+        let c = text[at..].chars().next();
+        self.add_capturing_thread(&mut current, &mut seen, 0, slots, at, (previous, c));
+        let mut found = None;
+        while !current.is_empty() {
+            let c = text[at..].chars().next();
+            let after = c.map(|c| (Some(c), text[at + c.len_utf8()..].chars().next()));
+            let mut next = Vec::new();
+            seen.iter_mut().for_each(|seen| *seen = false);
+            for (pc, mut slots) in current {
+                match &self.program[pc] {
+                    Inst::Match => {
+                        slots[0] = Some(match_start);
+                        slots[1] = Some(at);
+                        found = Some(slots);
+                        break;
+                    }
+                    inst => {
+                        if let (Some(c), Some(after)) = (c, after) {
+                            if self.consumes(inst, c) {
+                                let at = at + c.len_utf8();
+                                self.add_capturing_thread(
+                                    &mut next, &mut seen, pc + 1, slots, at, after,
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+            current = next;
+            match c {
+                Some(c) => at += c.len_utf8(),
+                None => break,
+            }
+        }
+        let slots = found?;
+        Some(
+            slots
+                .chunks(2)
+                .map(|group| group[0].zip(group[1]))
+                .collect(),
+        )
+    }
+
+    fn consumes(&self, inst: &Inst, c: char) -> bool {
+        match inst {
+            Inst::Char(expected) => {
+                *expected == c || (self.case_insensitive && chars_equal_ignoring_case(*expected, c))
+            }
+            Inst::Any => c != '\n',
+            Inst::Class(class) => class.matches(c, self.case_insensitive),
+            _ => false,
+        }
+    }
+
+    //follows jumps, splits and assertions from `pc` at a position between the characters
+    //`around`, adding the instructions that consume a character (or match) in priority order
+    fn add_thread(
+        &self,
+        threads: &mut Threads,
+        pc: usize,
+        start: usize,
+        around: (Option<char>, Option<char>),
+    ) {
+        let mut stack = vec![pc];
+        while let Some(pc) = stack.pop() {
+            if !threads.visit(pc) {
+                continue;
+            }
+            match self.step(pc, around) {
+                Step::Consume => threads.list.push((pc, start)),
+                Step::To(next) => stack.push(next),
+                Step::Split(first, second) => {
+                    stack.push(second);
+                    stack.push(first);
+                }
+                Step::Fail => (),
+            }
+        }
+    }
+
+    //`add_thread` for `captures_at`, the threads carrying where their groups start and end
+    fn add_capturing_thread(
+        &self,
+        threads: &mut Vec<(usize, Vec<Option<usize>>)>,
+        seen: &mut [bool],
+        pc: usize,
+        slots: Vec<Option<usize>>,
+        at: usize,
+        around: (Option<char>, Option<char>),
+    ) {
+        let mut stack = vec![(pc, slots)];
+        while let Some((pc, mut slots)) = stack.pop() {
+            if std::mem::replace(&mut seen[pc], true) {
+                continue;
+            }
+            if let Inst::Save(slot) = self.program[pc] {
+                slots[slot] = Some(at);
+                stack.push((pc + 1, slots));
+                continue;
+            }
+            match self.step(pc, around) {
+                Step::Consume => threads.push((pc, slots)),
+                Step::To(next) => stack.push((next, slots)),
+                Step::Split(first, second) => {
+                    stack.push((second, slots.clone()));
+                    stack.push((first, slots));
+                }
+                Step::Fail => (),
+            }
+        }
+    }
+
+    fn step(&self, pc: usize, around: (Option<char>, Option<char>)) -> Step {
+        let holds = |holds: bool| if holds { Step::To(pc + 1) } else { Step::Fail };
+        match &self.program[pc] {
+            Inst::Jump(to) => Step::To(*to),
+            Inst::Split(first, second) => Step::Split(*first, *second),
+            Inst::Save(_) => Step::To(pc + 1),
+            Inst::LineStart => holds(around.0.is_none()),
+            Inst::LineEnd => holds(around.1.is_none()),
+            Inst::WordBoundary { negated } => {
+                let boundary =
+                    around.0.is_some_and(is_word_char) != around.1.is_some_and(is_word_char);
+                holds(boundary != *negated)
+            }
+            _ => Step::Consume,
+        }
+    }
+}
+
+//the threads alive at one position: (instruction, where their match started)
+struct Threads {
+    list: Vec<(usize, usize)>,
+    seen: Vec<bool>,
+}
+
+impl Threads {
+    fn new(len: usize) -> Self {
+        Self {
+            list: Vec::new(),
+            seen: vec![false; len],
+        }
+    }
+
+    //false if `pc` was already reached at this position, by a thread with higher priority
+    fn visit(&mut self, pc: usize) -> bool {
+        !std::mem::replace(&mut self.seen[pc], true)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    fn clear(&mut self) {
+        self.list.clear();
+        self.seen.iter_mut().for_each(|seen| *seen = false);
+    }
+}
+
+//how many instructions `compile` turns `node` into, worked out without building them
+fn program_size(node: &Node) -> usize {
+    match node {
+        Node::Empty => 0,
+        Node::Char(_)
+        | Node::Any
+        | Node::Class(_)
+        | Node::LineStart
+        | Node::LineEnd
+        | Node::WordBoundary { .. } => 1,
+        Node::Concat(nodes) => nodes
+            .iter()
+            .fold(0usize, |size, node| size.saturating_add(program_size(node))),
+        Node::Group { node, .. } => program_size(node).saturating_add(2),
+        //a split and a jump around every alternative but the last
+        Node::Alternate(nodes) => nodes
+            .iter()
+            .fold(0usize, |size, node| {
+                size.saturating_add(program_size(node)).saturating_add(2)
+            })
+            .saturating_sub(2),
+        Node::Repeat { node, min, max, .. } => {
+            let body = program_size(node);
+            let optional = match max {
+                None => body.saturating_add(2),
+                Some(max) => body.saturating_add(1).saturating_mul((max - min) as usize),
+            };
+            body.saturating_mul(*min as usize).saturating_add(optional)
+        }
+    }
+}
+
+fn compile(node: &Node, program: &mut Vec<Inst>) {
+    match node {
+        Node::Empty => (),
+        Node::Char(c) => program.push(Inst::Char(*c)),
+        Node::Any => program.push(Inst::Any),
+        Node::Class(class) => program.push(Inst::Class(class.clone())),
+        Node::LineStart => program.push(Inst::LineStart),
+        Node::LineEnd => program.push(Inst::LineEnd),
+        Node::WordBoundary { negated } => program.push(Inst::WordBoundary { negated: *negated }),
+        Node::Concat(nodes) => nodes.iter().for_each(|node| compile(node, program)),
+        Node::Group { index, node } => {
+            program.push(Inst::Save(2 * index));
+            compile(node, program);
+            program.push(Inst::Save(2 * index + 1));
+        }
+        Node::Alternate(nodes) => {
+            let mut jumps = Vec::new();
+            for (i, node) in nodes.iter().enumerate() {
+                if i + 1 == nodes.len() {
+                    compile(node, program);
+                    break;
+                }
+                let split = program.len();
+                program.push(Inst::Split(split + 1, 0));
+                compile(node, program);
+                jumps.push(program.len());
+                program.push(Inst::Jump(0));
+                let next = program.len();
+                program[split] = Inst::Split(split + 1, next);
+            }
+            let end = program.len();
+            for jump in jumps {
+                program[jump] = Inst::Jump(end);
+            }
+        }
+        Node::Repeat {
+            node,
+            min,
+            max,
+            greedy,
+        } => {
+            for _ in 0..*min {
+                compile(node, program);
+            }
+            let split = |program: &mut Vec<Inst>, at: usize, body: usize, out: usize| {
+                program[at] = if *greedy {
+                    Inst::Split(body, out)
+                } else {
+                    Inst::Split(out, body)
+                };
+            };
+            match max {
+                None => {
+                    let start = program.len();
+                    program.push(Inst::Split(0, 0));
+                    compile(node, program);
+                    program.push(Inst::Jump(start));
+                    let end = program.len();
+                    split(program, start, start + 1, end);
+                }
+                Some(max) => {
+                    let mut splits = Vec::new();
+                    for _ in *min..*max {
+                        splits.push(program.len());
+                        program.push(Inst::Split(0, 0));
+                        compile(node, program);
+                    }
+                    let end = program.len();
+                    for at in splits {
+                        split(program, at, at + 1, end);
+                    }
+                }
+            }
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+    //capturing groups opened so far
+    groups: usize,
+}
+
+impl Parser {
+    fn new(pattern: &str) -> Self {
+        Self {
+            chars: pattern.chars().collect(),
+            position: 0,
+            groups: 0,
+        }
+    }
+
+    fn parse(&mut self) -> Result<Node, Error> {
+        let node = self.alternation()?;
+        match self.peek() {
+            Some(')') => Err(self.error("unmatched )")),
+            Some(_) => Err(self.error("unexpected character")),
+            None => Ok(node),
+        }
+    }
+
+    fn error(&self, message: &str) -> Error {
+        Error {
+            message: message.to_string(),
+            position: self.position,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += 1;
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn alternation(&mut self) -> Result<Node, Error> {
+        let mut alternatives = vec![self.concatenation()?];
+        while self.eat('|') {
+            alternatives.push(self.concatenation()?);
+        }
+        Ok(if alternatives.len() == 1 {
+            alternatives.remove(0)
+        } else {
+            Node::Alternate(alternatives)
+        })
+    }
+
+    fn concatenation(&mut self) -> Result<Node, Error> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.atom()?;
+            nodes.push(self.repetition(atom)?);
+        }
+        Ok(match nodes.len() {
+            0 => Node::Empty,
+            1 => nodes.remove(0),
+            _ => Node::Concat(nodes),
+        })
+    }
+
+    fn atom(&mut self) -> Result<Node, Error> {
+        let start = self.position;
+        let c = match self.next() {
+            Some(c) => c,
+            None => return Err(self.error("unexpected end of pattern")),
+        };
+        Ok(match c {
+            '.' => Node::Any,
+            '^' => Node::LineStart,
+            '$' => Node::LineEnd,
+            '[' => Node::Class(self.class()?),
+            '(' => {
+                //(?:...) only groups, a plain group also captures
+                let capturing = !self.eat('?');
+                if !capturing && !self.eat(':') {
+                    return Err(self.error("unsupported group flag"));
+                }
+                let index = if capturing {
+                    self.groups += 1;
+                    self.groups
+                } else {
+                    0
+                };
+                let node = self.alternation()?;
+                if !self.eat(')') {
+                    return Err(Error {
+                        message: "unclosed group".to_string(),
+                        position: start,
+                    });
+                }
+                if capturing {
+                    Node::Group {
+                        index,
+                        node: Box::new(node),
+                    }
+                } else {
+                    node
+                }
+            }
+            '*' | '+' | '?' => return Err(self.error("nothing to repeat")),
+            '\\' => self.escape()?,
+            c => Node::Char(c),
+        })
+    }
+
+    fn escape(&mut self) -> Result<Node, Error> {
+        let c = match self.next() {
+            Some(c) => c,
+            None => return Err(self.error("trailing backslash")),
+        };
+        if let Some((perl, negated)) = perl_class(c) {
+            return Ok(Node::Class(Class {
+                ranges: Vec::new(),
+                perl: vec![(perl, negated)],
+                negated: false,
+            }));
+        }
+        Ok(match c {
+            'b' => Node::WordBoundary { negated: false },
+            'B' => Node::WordBoundary { negated: true },
+            c => Node::Char(escaped_char(c)),
+        })
+    }
+
+    //what follows `[`, up to and including `]`. A `]` right at the start is a literal one.
+    fn class(&mut self) -> Result<Class, Error> {
+        let start = self.position - 1;
+        let mut class = Class {
+            ranges: Vec::new(),
+            perl: Vec::new(),
+            negated: self.eat('^'),
+        };
+        let mut first = true;
+        loop {
+            let c = match self.next() {
+                Some(c) => c,
+                None => {
+                    return Err(Error {
+                        message: "unclosed character class".to_string(),
+                        position: start,
+                    })
+                }
+            };
+            if c == ']' && !first {
+                return Ok(class);
+            }
+            first = false;
+            let low = if c == '\\' {
+                let escaped = self
+                    .next()
+                    .ok_or_else(|| self.error("trailing backslash"))?;
+                if let Some(perl) = perl_class(escaped) {
+                    class.perl.push(perl);
+                    continue;
+                }
+                escaped_char(escaped)
+            } else {
+                c
+            };
+            let is_range = self.peek() == Some('-')
+                && self.chars.get(self.position + 1).is_some_and(|c| *c != ']');
+            if !is_range {
+                class.ranges.push((low, low));
+                continue;
+            }
+            self.position += 1;
+            let high = match self.next() {
+                Some('\\') => escaped_char(
+                    self.next()
+                        .ok_or_else(|| self.error("trailing backslash"))?,
+                ),
+                Some(high) => high,
+                None => return Err(self.error("unclosed character class")),
+            };
+            if high < low {
+                return Err(self.error("invalid range"));
+            }
+            class.ranges.push((low, high));
+        }
+    }
+
+    //after a `*`, `+`, `?` or `{n,m}` the repeated atom, followed by `?` to make it lazy
+    fn repetition(&mut self, node: Node) -> Result<Node, Error> {
+        let (min, max) = match self.peek() {
+            Some('{') => match self.counts()? {
+                Some(counts) => counts,
+                None => return Ok(node),
+            },
+            Some(c @ ('*' | '+' | '?')) => {
+                self.position += 1;
+                match c {
+                    '*' => (0, None),
+                    '+' => (1, None),
+                    _ => (0, Some(1)),
+                }
+            }
+            _ => return Ok(node),
+        };
+        if matches!(
+            node,
+            Node::LineStart | Node::LineEnd | Node::WordBoundary { .. }
+        ) {
+            return Err(self.error("nothing to repeat"));
+        }
+        let greedy = !self.eat('?');
+        let node = Node::Repeat {
+            node: Box::new(node),
+            min,
+            max,
+            greedy,
+        };
+        if matches!(self.peek(), Some('*' | '+' | '?')) {
+            return Err(self.error("nothing to repeat"));
+        }
+        Ok(node)
+    }
+
+    //`{n}`, `{n,}` or `{n,m}`, consumed; None and nothing consumed if the brace is a literal one
+    fn counts(&mut self) -> Result<Option<(u32, Option<u32>)>, Error> {
+        let start = self.position;
+        let close = match self.chars[start..].iter().position(|c| *c == '}') {
+            Some(close) => start + close,
+            None => return Ok(None),
+        };
+        let inner: String = self.chars[start + 1..close].iter().collect();
+        let (min, max) = match inner.split_once(',') {
+            Some((min, "")) => (min.parse().ok(), Some(None)),
+            Some((min, max)) => (min.parse().ok(), max.parse().ok().map(Some)),
+            None => (inner.parse().ok(), inner.parse().ok().map(Some)),
+        };
+        let (min, max) = match (min, max) {
+            (Some(min), Some(max)) => (min, max),
+            _ => return Ok(None),
+        };
+        if max.is_some_and(|max| max < min) {
+            return Err(self.error("invalid repetition range"));
+        }
+        if max.unwrap_or(min) > MAX_REPEAT {
+            return Err(self.error("repetition count too large"));
+        }
+        self.position = close + 1;
+        Ok(Some((min, max)))
+    }
+}
+
+//`text` as a pattern matching exactly itself
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        if "\\.+*?()|[]{}^$".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn perl_class(c: char) -> Option<(Perl, bool)> {
+    match c {
+        'd' => Some((Perl::Digit, false)),
+        'D' => Some((Perl::Digit, true)),
+        'w' => Some((Perl::Word, false)),
+        'W' => Some((Perl::Word, true)),
+        's' => Some((Perl::Space, false)),
+        'S' => Some((Perl::Space, true)),
+        _ => None,
+    }
+}
+
+fn escaped_char(c: char) -> char {
+    match c {
+        't' => '\t',
+        'n' => '\n',
+        'r' => '\r',
+        c => c,
+    }
+}
+
+impl Class {
+    fn matches(&self, c: char, case_insensitive: bool) -> bool {
+        let contains = |c: char| {
+            self.ranges
+                .iter()
+                .any(|(low, high)| (*low..=*high).contains(&c))
+                || self
+                    .perl
+                    .iter()
+                    .any(|(perl, negated)| perl.matches(c) != *negated)
+        };
+        let found = contains(c)
+            || (case_insensitive
+                && casefold::variants(c)
+                    .iter()
+                    .any(|other| *other != c && contains(*other)));
+        found != self.negated
+    }
+}
+
+impl Perl {
+    fn matches(self, c: char) -> bool {
+        match self {
+            Perl::Digit => c.is_ascii_digit(),
+            Perl::Word => is_word_char(c),
+            Perl::Space => c.is_whitespace(),
+        }
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+//simple folding, so a pattern character matches one character of the text
+fn chars_equal_ignoring_case(a: char, b: char) -> bool {
+    casefold::simple_fold(a) == casefold::simple_fold(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //(pattern, text, what should match), None where nothing should
+    const CASES: &[(&str, &str, Option<&str>)] = &[
+        ("abc", "xabcx", Some("abc")),
+        ("abc", "ab", None),
+        ("", "abc", Some("")),
+        ("a.c", "abc", Some("abc")),
+        ("a.c", "ac", None),
+        ("^ab", "abab", Some("ab")),
+        ("^b", "ab", None),
+        ("b$", "abb", Some("b")),
+        ("a$", "ab", None),
+        ("^$", "", Some("")),
+        ("a*", "aaab", Some("aaa")),
+        ("a*", "baaa", Some("")),
+        ("ba+", "cbaaa", Some("baaa")),
+        ("ba+", "cb", None),
+        ("colou?r", "color", Some("color")),
+        ("colou?r", "colour", Some("colour")),
+        ("a{2}", "aaaa", Some("aa")),
+        ("a{2,}", "aaaa", Some("aaaa")),
+        ("a{1,3}", "aaaa", Some("aaa")),
+        ("a{2,3}", "a", None),
+        ("a{,2}", "a{,2}", Some("a{,2}")),
+        ("x{", "x{", Some("x{")),
+        ("a}*", "a}}}", Some("a}}}")),
+        ("a+?", "aaa", Some("a")),
+        ("a*?b", "aab", Some("aab")),
+        ("<.+>", "<a><b>", Some("<a><b>")),
+        ("<.+?>", "<a><b>", Some("<a>")),
+        ("cat|dog", "hotdog", Some("dog")),
+        ("cat|category", "category", Some("cat")),
+        ("a|b|c", "xxc", Some("c")),
+        ("gr(a|e)y", "grey", Some("grey")),
+        ("gr(?:a|e)y", "gray", Some("gray")),
+        ("(ab)+", "ababa", Some("abab")),
+        ("(a|ab)(c|bcd)", "abcd", Some("abcd")),
+        ("(a*)*b", "aaab", Some("aaab")),
+        ("(a|)+b", "ab", Some("ab")),
+        ("[abc]+", "xxbcay", Some("bca")),
+        ("[a-z]+", "ABcdE", Some("cd")),
+        ("[^a-z]+", "abCD1e", Some("CD1")),
+        ("[]a]+", "x]a]", Some("]a]")),
+        ("[a-]+", "x-a-", Some("-a-")),
+        ("[\\d.]+", "v1.25x", Some("1.25")),
+        ("[\\]]", "a]", Some("]")),
+        ("\\d+", "abc 123", Some("123")),
+        ("\\D+", "12ab3", Some("ab")),
+        ("\\w+", "  foo_bar1 ", Some("foo_bar1")),
+        ("\\W+", "ab, cd", Some(", ")),
+        ("\\s+", "a \tb", Some(" \t")),
+        ("\\S+", "  ab ", Some("ab")),
+        ("\\bcat\\b", "concat cat", Some("cat")),
+        ("\\Bcat", "cat concat", Some("cat")),
+        ("a\\.b", "axb a.b", Some("a.b")),
+        ("\\(x\\)", "f(x)", Some("(x)")),
+        ("a\\tb", "a\tb", Some("a\tb")),
+        ("é+", "caféé", Some("éé")),
+        ("[α-ω]+", "abγδε", Some("γδε")),
+        ("(x+x+)+y", "xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx", None),
+    ];
+
+    #[test]
+    fn matches_like_the_reference() {
+        for (pattern, text, expected) in CASES {
+            let regex = Regex::new(pattern, false).unwrap();
+            let found = regex.find_at(text, 0).map(|(start, end)| &text[start..end]);
+            assert_eq!(found, *expected, "pattern {:?} on {:?}", pattern, text);
+        }
+    }
+
+    #[test]
+    fn ignores_case_and_finds_later_matches() {
+        let regex = Regex::new("straße|[a-c]x", true).unwrap();
+        assert!(regex.is_match("STRASSE STRAßE"));
+        assert_eq!(regex.find_at("BX Cx", 1), Some((3, 5)));
+        assert!(!Regex::new("AND", false).unwrap().is_match("and"));
+
+        //one character of the pattern for one of the text, so ẞ but not SS for ß
+        let regex = Regex::new("ß", true).unwrap();
+        assert_eq!(regex.find_at("SS ẞ", 0), Some((3, 6)));
+        let regex = Regex::new("οδοσ [σ]", true).unwrap();
+        assert!(regex.is_match("ΟΔΟΣ ς"));
+        assert!(regex.is_match("οδος Σ"));
+        assert!(!Regex::new("[i]", true).unwrap().is_match("ıİ"));
+        assert!(!Regex::new("ı", true).unwrap().is_match("Ii"));
+        assert!(Regex::new("[a-z]", true).unwrap().is_match("K"));
+    }
+
+    #[test]
+    fn captures_groups() {
+        let regex = Regex::new(r"(\w+)@(\w+)(?:\.(com|org))?", false).unwrap();
+        assert_eq!(regex.groups(), 3);
+        assert_eq!(
+            regex.captures_at("mail bob@example now", 0),
+            Some(vec![Some((5, 16)), Some((5, 8)), Some((9, 16)), None])
+        );
+        assert_eq!(
+            regex.captures_at("a@b.org c@d", 1),
+            Some(vec![Some((8, 11)), Some((8, 9)), Some((10, 11)), None])
+        );
+        //the last time round a repetition is what a group keeps
+        let regex = Regex::new("(a|b)+(b)?", false).unwrap();
+        assert_eq!(
+            regex.captures_at("xabab", 0),
+            Some(vec![Some((1, 5)), Some((4, 5)), None])
+        );
+        assert_eq!(regex.captures_at("xyz", 0), None);
+    }
+
+    #[test]
+    fn escaped_text_matches_itself() {
+        let text = "a.b*(c|d)[e]{2}^$\\";
+        let regex = Regex::new(&escape(text), false).unwrap();
+        assert_eq!(regex.find_at(text, 0), Some((0, text.len())));
+        assert!(!regex.is_match("axb"));
+    }
+
+    #[test]
+    fn sizes_programs_before_compiling_them() {
+        for pattern in [
+            "",
+            "a|b|",
+            "(a|bc)*d",
+            "x{2,5}?",
+            "(?:a{3}|\\b[a-z]+){2,}$",
+            "((a){2}){3}",
+        ] {
+            let node = Parser::new(pattern).parse().unwrap();
+            let mut program = Vec::new();
+            compile(&node, &mut program);
+            assert_eq!(program_size(&node), program.len(), "pattern {:?}", pattern);
+        }
+        //nested counts as large as allowed one at a time still compile
+        assert!(Regex::new("((a{100}){100}){10}", false).is_ok());
+    }
+
+    #[test]
+    fn reports_bad_patterns() {
+        for (pattern, message, position) in [
+            ("(ab", "unclosed group", 0),
+            ("ab)", "unmatched )", 2),
+            ("[ab", "unclosed character class", 0),
+            ("*a", "nothing to repeat", 1),
+            ("a**", "nothing to repeat", 2),
+            ("[z-a]", "invalid range", 4),
+            ("a{3,2}", "invalid repetition range", 1),
+            ("a{5000}", "repetition count too large", 1),
+            ("((a{1000}){1000}){1000}", "pattern too large", 0),
+            ("(a{1000}|b){1000}", "pattern too large", 0),
+            ("a\\", "trailing backslash", 2),
+            ("(?i)a", "unsupported group flag", 2),
+        ] {
+            assert_eq!(
+                Regex::new(pattern, false).unwrap_err(),
+                Error {
+                    message: message.to_string(),
+                    position
+                },
+                "pattern {:?}",
+                pattern
+            );
+        }
+    }
+}
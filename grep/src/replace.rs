@@ -0,0 +1,297 @@
+use crate::matcher::Matcher;
+use std::fs;
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::process;
+
+//unchanged lines shown around each change in a diff
+const DIFF_CONTEXT: usize = 3;
+
+//every match in `line`, empty ones included, with the replacement for it. In `template`, `$1`
+//or `${1}` stands for what group 1 matched, `$0` for the whole match and `$$` for a `$`.
+pub fn replacements(
+    matcher: &dyn Matcher,
+    template: &str,
+    line: &str,
+) -> Vec<(Range<usize>, String)> {
+    let mut found = Vec::new();
+    let mut start = 0;
+    while start <= line.len() {
+        let groups = match matcher.captures_at(line, start) {
+            Some(groups) => groups,
+            None => break,
+        };
+        let (match_start, match_end) = match groups[0] {
+            Some(range) => range,
+            None => break,
+        };
+        found.push((match_start..match_end, expand(template, line, &groups)));
+        start = if match_end > match_start {
+            match_end
+        } else {
+            //an empty match, the next one is at least a character later
+            match line[match_end..].chars().next() {
+                Some(c) => match_end + c.len_utf8(),
+                None => break,
+            }
+        };
+    }
+    found
+}
+
+//`line` with every match replaced
+pub fn replace_line(matcher: &dyn Matcher, template: &str, line: &str) -> String {
+    let mut replaced = String::new();
+    let mut copied = 0;
+    for (range, replacement) in replacements(matcher, template, line) {
+        replaced.push_str(&line[copied..range.start]);
+        replaced.push_str(&replacement);
+        copied = range.end;
+    }
+    replaced.push_str(&line[copied..]);
+    replaced
+}
+
+fn expand(template: &str, line: &str, groups: &[Option<(usize, usize)>]) -> String {
+    let mut expanded = String::new();
+    let mut rest = template;
+    while let Some(dollar) = rest.find('$') {
+        expanded.push_str(&rest[..dollar]);
+        rest = &rest[dollar + 1..];
+        if let Some(after) = rest.strip_prefix('$') {
+            expanded.push('$');
+            rest = after;
+            continue;
+        }
+        let (digits, after) = match rest.strip_prefix('{') {
+            Some(braced) => match braced.split_once('}') {
+                Some((digits, after)) => (digits, after),
+                None => ("", rest),
+            },
+            None => {
+                let end = rest
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(rest.len());
+                (&rest[..end], &rest[end..])
+            }
+        };
+        match digits.parse::<usize>() {
+            //a group that does not exist or took no part stands for nothing
+            Ok(group) => {
+                if let Some(Some((start, end))) = groups.get(group) {
+                    expanded.push_str(&line[*start..*end]);
+                }
+                rest = after;
+            }
+            //a `$` that is not a reference is itself
+            Err(_) => expanded.push('$'),
+        }
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+//the lines of `content` with their endings, and what each became if it had a match replaced.
+//Line endings are kept as they are.
+pub fn replace_lines<'a>(
+    matcher: &dyn Matcher,
+    template: &str,
+    content: &'a str,
+) -> Vec<(&'a str, Option<String>)> {
+    content
+        .split_inclusive('\n')
+        .map(|line| {
+            let body = line.strip_suffix('\n').unwrap_or(line);
+            let body = body.strip_suffix('\r').unwrap_or(body);
+            let replaced = matcher
+                .is_match(body)
+                .then(|| replace_line(matcher, template, body) + &line[body.len()..]);
+            (line, replaced.filter(|replaced| replaced != line))
+        })
+        .collect()
+}
+
+//the content of the file once replaced
+pub fn joined(lines: &[(&str, Option<String>)]) -> String {
+    lines
+        .iter()
+        .map(|(old, new)| new.as_deref().unwrap_or(old))
+        .collect()
+}
+
+//writes a unified diff of what `replace_lines` changed
+pub fn write_diff(
+    out: &mut dyn Write,
+    name: &str,
+    lines: &[(&str, Option<String>)],
+) -> io::Result<()> {
+    let changed: Vec<usize> = (0..lines.len()).filter(|&i| lines[i].1.is_some()).collect();
+    if changed.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(out, "--- {}", name)?;
+    writeln!(out, "+++ {}", name)?;
+    let mut hunk_start = 0;
+    while hunk_start < changed.len() {
+        //changes close enough for their context to touch are one hunk
+        let mut hunk_end = hunk_start + 1;
+        while hunk_end < changed.len()
+            && changed[hunk_end] - changed[hunk_end - 1] <= 2 * DIFF_CONTEXT + 1
+        {
+            hunk_end += 1;
+        }
+        let from = changed[hunk_start].saturating_sub(DIFF_CONTEXT);
+        let to = (changed[hunk_end - 1] + DIFF_CONTEXT + 1).min(lines.len());
+        let new_from: usize = lines[..from].iter().map(|line| new_lines(line).len()).sum();
+        let new_count: usize = lines[from..to]
+            .iter()
+            .map(|line| new_lines(line).len())
+            .sum();
+        writeln!(
+            out,
+            "@@ -{},{} +{},{} @@",
+            from + 1,
+            to - from,
+            new_from + 1,
+            new_count
+        )?;
+        for line in &lines[from..to] {
+            if line.1.is_none() {
+                write_diff_line(out, ' ', line.0)?;
+                continue;
+            }
+            write_diff_line(out, '-', line.0)?;
+            for new_line in new_lines(line) {
+                write_diff_line(out, '+', new_line)?;
+            }
+        }
+        hunk_start = hunk_end;
+    }
+    Ok(())
+}
+
+//a replacement can bring its own line breaks, so a line can become several
+fn new_lines<'a>((old, new): &'a (&str, Option<String>)) -> Vec<&'a str> {
+    match new {
+        Some(new) => new.split_inclusive('\n').collect(),
+        None => vec![*old],
+    }
+}
+
+fn write_diff_line(out: &mut dyn Write, marker: char, line: &str) -> io::Result<()> {
+    write!(out, "{}{}", marker, line)?;
+    if !line.ends_with('\n') {
+        writeln!(out)?;
+        writeln!(out, "\\ No newline at end of file")?;
+    }
+    Ok(())
+}
+
+//replaces `file` with `content` all at once: written next to it first, then renamed over it, so
+//a reader never sees half a file. A symlink is followed, so the file it points to is replaced
+//rather than the link. The permissions are kept, the owner and extended attributes are not: the
+//new file belongs to whoever runs grep.
+pub fn write_atomically(file: &Path, content: &str) -> io::Result<()> {
+    let file = &fs::canonicalize(file)?;
+    let permissions = fs::metadata(file)?.permissions();
+    let name = file.file_name().unwrap_or_default().to_string_lossy();
+    let temporary = file.with_file_name(format!(".{}.grep-{}", name, process::id()));
+    let result = fs::write(&temporary, content)
+        .and_then(|()| fs::set_permissions(&temporary, permissions))
+        .and_then(|()| fs::rename(&temporary, file));
+    if result.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matcher::FixedMatcher;
+    use crate::regex::Regex;
+
+    #[test]
+    fn expands_group_references() {
+        let regex = Regex::new(r"(\w+)=(\d+)?", false).unwrap();
+        let replaced = |template| replace_line(&regex, template, "a=1, b=, c=3");
+        assert_eq!(replaced("$2:$1"), "1:a, :b, 3:c");
+        assert_eq!(replaced("${1}x"), "ax, bx, cx");
+        assert_eq!(replaced("$1x"), "ax, bx, cx");
+        assert_eq!(
+            replaced("[$0] $$5 $x ${"),
+            "[a=1] $5 $x ${, [b=] $5 $x ${, [c=3] $5 $x ${"
+        );
+        assert_eq!(replaced("$9"), ", , ");
+        //empty matches are replaced too
+        let start = Regex::new("^|$", false).unwrap();
+        assert_eq!(replace_line(&start, "|", "line"), "|line|");
+        let fixed = FixedMatcher::new("a.", true);
+        assert_eq!(replace_line(&fixed, "<$0$1>", "A.b a.c"), "<A.>b <a.>c");
+    }
+
+    #[test]
+    fn diffs_what_was_replaced() {
+        let old: String = (1..=12)
+            .map(|i| format!("line {}\r\n", i))
+            .collect::<String>()
+            + "end";
+        let regex = Regex::new(r"^line (2|10)$|end", false).unwrap();
+        let lines = replace_lines(&regex, "new:$1\nsplit", &old);
+        let new = joined(&lines);
+        assert!(new.starts_with("line 1\r\nnew:2\nsplit\r\nline 3\r\n"));
+        assert!(new.ends_with("line 12\r\nnew:\nsplit"));
+        let unchanged = replace_lines(&regex, "$0", &old);
+        assert!(unchanged.iter().all(|(_, new)| new.is_none()));
+
+        let mut diff = Vec::new();
+        write_diff(&mut diff, "file.txt", &lines).unwrap();
+        let expected = "\
+--- file.txt
++++ file.txt
+@@ -1,5 +1,6 @@
+ line 1\r
+-line 2\r
++new:2
++split\r
+ line 3\r
+ line 4\r
+ line 5\r
+@@ -7,7 +8,9 @@
+ line 7\r
+ line 8\r
+ line 9\r
+-line 10\r
++new:10
++split\r
+ line 11\r
+ line 12\r
+-end
+\\ No newline at end of file
++new:
++split
+\\ No newline at end of file
+";
+        assert_eq!(String::from_utf8(diff).unwrap(), expected);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn writes_through_symlinks() {
+        let dir = std::env::temp_dir().join(format!("grep-atomic-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (target, link) = (dir.join("target.txt"), dir.join("link.txt"));
+        fs::write(&target, "old\n").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+        write_atomically(&link, "new\n").unwrap();
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new\n");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,277 @@
+use crate::error::Error;
+use crate::glob::glob_match;
+use crate::ignore::{self, IgnoreFile};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+//what --type accepts and the file names each stands for
+pub const FILE_TYPES: &[(&str, &[&str])] = &[
+    ("c", &["*.c", "*.h"]),
+    ("cpp", &["*.cpp", "*.cc", "*.cxx", "*.hpp", "*.hh", "*.hxx"]),
+    ("css", &["*.css"]),
+    ("go", &["*.go"]),
+    ("html", &["*.html", "*.htm"]),
+    ("js", &["*.js", "*.mjs", "*.cjs"]),
+    ("json", &["*.json"]),
+    ("md", &["*.md", "*.markdown"]),
+    ("py", &["*.py"]),
+    ("rust", &["*.rs"]),
+    ("sh", &["*.sh", "*.bash"]),
+    ("toml", &["*.toml", "Cargo.lock"]),
+    ("ts", &["*.ts", "*.tsx"]),
+    ("txt", &["*.txt"]),
+    ("yaml", &["*.yaml", "*.yml"]),
+];
+
+//which files a directory search looks at
+#[derive(Default)]
+pub struct WalkOptions {
+    //--hidden: also files and directories whose name starts with a dot
+    pub hidden: bool,
+    //--no-ignore: .gitignore and .ignore files are not read
+    pub no_ignore: bool,
+    //--glob: a file has to match one of these unless there are none,
+    //a glob starting with `!` leaves out what it matches
+    pub globs: Vec<String>,
+    //the globs of the --type arguments, a file has to match one unless there are none
+    pub type_globs: Vec<&'static str>,
+}
+
+impl WalkOptions {
+    //false if `name` is not a known file type
+    pub fn add_type(&mut self, name: &str) -> bool {
+        match FILE_TYPES.iter().find(|(type_name, _)| *type_name == name) {
+            Some((_, globs)) => {
+                self.type_globs.extend_from_slice(globs);
+                true
+            }
+            None => false,
+        }
+    }
+
+    //`relative` is the path below the directory given on the command line
+    fn includes_file(&self, relative: &str) -> bool {
+        let name = relative.rsplit('/').next().unwrap_or_default();
+        let glob_matches = |glob: &str| {
+            if glob.contains('/') {
+                glob_match(glob.trim_start_matches('/'), relative)
+            } else {
+                glob_match(glob, name)
+            }
+        };
+        let (excludes, includes): (Vec<&String>, Vec<&String>) =
+            self.globs.iter().partition(|glob| glob.starts_with('!'));
+        if excludes.iter().any(|glob| glob_matches(&glob[1..])) {
+            return false;
+        }
+        (includes.is_empty() || includes.iter().any(|glob| glob_matches(glob)))
+            && (self.type_globs.is_empty()
+                || self.type_globs.iter().any(|glob| glob_match(glob, name)))
+    }
+
+    fn excludes_dir(&self, relative: &str) -> bool {
+        let name = relative.rsplit('/').next().unwrap_or_default();
+        self.globs
+            .iter()
+            .filter_map(|glob| glob.strip_prefix('!'))
+            .any(|glob| glob_match(glob, if glob.contains('/') { relative } else { name }))
+    }
+}
+
+//the files to search, in order. Files named on the command line are always searched,
+//directories are walked with hidden entries, ignored entries and filtered out files left out.
+//A directory below one given that cannot be read is passed to `on_error` and skipped.
+pub fn files(
+    paths: &[String],
+    options: &WalkOptions,
+    on_error: &mut dyn FnMut(&Path, io::Error),
+) -> Result<Vec<PathBuf>, Error> {
+    let mut files = Vec::new();
+    for path in paths {
+        let path = Path::new(path);
+        let path_error = |source| Error::Path {
+            path: path.to_path_buf(),
+            source,
+        };
+        if path != Path::new(crate::STDIN_PATH) && fs::metadata(path).map_err(path_error)?.is_dir()
+        {
+            let ignore_files = if options.no_ignore {
+                Vec::new()
+            } else {
+                enclosing_ignore_files(path)
+            };
+            walk_dir(
+                path,
+                path,
+                &absolute(path),
+                options,
+                ignore_files,
+                &mut files,
+                on_error,
+            )
+            .map_err(path_error)?;
+        } else {
+            files.push(path.to_path_buf());
+        }
+    }
+    Ok(files)
+}
+
+//`absolute_dir` is `dir` made absolute, which is how ignore files know their directory
+fn walk_dir(
+    root: &Path,
+    dir: &Path,
+    absolute_dir: &Path,
+    options: &WalkOptions,
+    mut ignore_files: Vec<IgnoreFile>,
+    files: &mut Vec<PathBuf>,
+    on_error: &mut dyn FnMut(&Path, io::Error),
+) -> io::Result<()> {
+    if !options.no_ignore {
+        for name in ignore::IGNORE_FILES {
+            ignore_files.extend(IgnoreFile::read(absolute_dir, name));
+        }
+    }
+    let mut entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name == ".git" || (name.starts_with('.') && !options.hidden) {
+            continue;
+        }
+        let file_type = entry.file_type()?;
+        //symlinked directories are not followed, they could lead back up the tree
+        let is_dir = file_type.is_dir();
+        let is_file = file_type.is_file() || (file_type.is_symlink() && path.is_file());
+        let absolute_path = absolute_dir.join(entry.file_name());
+        if ignore::is_ignored(&ignore_files, &absolute_path, is_dir) {
+            continue;
+        }
+        let relative = path
+            .strip_prefix(root)
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .replace('\\', "/");
+        if is_dir && !options.excludes_dir(&relative) {
+            //a directory we cannot read is skipped like a file we cannot read
+            let ignore_files = ignore_files.clone();
+            let walked = walk_dir(
+                root,
+                &path,
+                &absolute_path,
+                options,
+                ignore_files,
+                files,
+                on_error,
+            );
+            if let Err(error) = walked {
+                on_error(&path, error);
+            }
+        } else if is_file && options.includes_file(&relative) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+//the ignore files in the directories above one given on the command line, outermost first,
+//up to the root of the git repository it is in. Outside a repository there are none.
+fn enclosing_ignore_files(dir: &Path) -> Vec<IgnoreFile> {
+    let dir = absolute(dir);
+    let repository = match dir
+        .ancestors()
+        .find(|ancestor| ancestor.join(".git").exists())
+    {
+        Some(repository) => repository,
+        None => return Vec::new(),
+    };
+    let mut dirs: Vec<&Path> = dir
+        .ancestors()
+        .skip(1)
+        .take_while(|ancestor| ancestor.starts_with(repository))
+        .collect();
+    dirs.reverse();
+    dirs.into_iter()
+        .flat_map(|dir| {
+            ignore::IGNORE_FILES
+                .iter()
+                .filter_map(move |name| IgnoreFile::read(dir, name))
+        })
+        .collect()
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn walks_directories_in_order_leaving_out_what_is_ignored() {
+        let dir = std::env::temp_dir().join(format!("grep-walk-{}", std::process::id()));
+        for sub in ["src/bin", "target", ".hidden", "docs"] {
+            fs::create_dir_all(dir.join(sub)).unwrap();
+        }
+        for file in [
+            "src/lib.rs",
+            "src/bin/main.rs",
+            "target/out.rs",
+            ".hidden/secret.rs",
+            ".env",
+            "docs/guide.md",
+            "docs/notes.txt",
+            "Cargo.toml",
+            "debug.log",
+        ] {
+            fs::write(dir.join(file), "text\n").unwrap();
+        }
+        fs::write(dir.join(".gitignore"), "target/\n*.log\n").unwrap();
+        fs::write(dir.join("docs/.ignore"), "*.txt\n").unwrap();
+
+        let root = dir.to_string_lossy().to_string();
+        let listed = |options: &WalkOptions, paths: &[&str]| -> Vec<String> {
+            let paths: Vec<String> = paths.iter().map(|path| path.to_string()).collect();
+            files(&paths, options, &mut |dir, error| {
+                panic!("{}: {}", dir.display(), error)
+            })
+            .unwrap()
+            .iter()
+            .map(|file| {
+                let file = file.strip_prefix(&dir).unwrap_or(file);
+                file.to_string_lossy().replace('\\', "/")
+            })
+            .collect()
+        };
+        let mut options = WalkOptions::default();
+        assert_eq!(
+            listed(&options, &[&root]),
+            [
+                "Cargo.toml",
+                "docs/guide.md",
+                "src/bin/main.rs",
+                "src/lib.rs"
+            ]
+        );
+
+        options.hidden = true;
+        options.globs = vec!["*.rs".to_string(), "!bin".to_string()];
+        assert_eq!(
+            listed(&options, &[&root]),
+            [".hidden/secret.rs", "src/lib.rs"]
+        );
+
+        let mut options = WalkOptions::default();
+        assert!(options.add_type("md") && options.add_type("toml"));
+        assert!(!options.add_type("cobol"));
+        let debug_log = dir.join("debug.log").to_string_lossy().to_string();
+        assert_eq!(
+            listed(&options, &[&root, &debug_log]),
+            ["Cargo.toml", "docs/guide.md", "debug.log"]
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,201 @@
+use crate::Position;
+use std::collections::BTreeMap;
+
+//older jumps are forgotten
+const MAX_JUMPS: usize = 100;
+
+//named positions in a document plus the places the cursor jumped away from.
+//The document keeps them on the same text as lines are added and removed above them.
+#[derive(Default, Clone)]
+pub struct Bookmarks {
+    marks: BTreeMap<char, Position>,
+    jumps: Vec<Position>,
+    //where going back starts from, jumps.len() unless we are in the middle of the list
+    jump_index: usize,
+}
+
+impl Bookmarks {
+    pub fn set(&mut self, name: char, position: Position) {
+        self.marks.insert(name, position);
+    }
+
+    pub fn get(&self, name: char) -> Option<Position> {
+        self.marks.get(&name).copied()
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = char> + '_ {
+        self.marks.keys().copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.marks.is_empty()
+    }
+
+    //the bookmark on line `y`, the first by name if there are several
+    pub fn on_line(&self, y: usize) -> Option<char> {
+        self.marks
+            .iter()
+            .find(|(_, position)| position.y == y)
+            .map(|(name, _)| *name)
+    }
+
+    //remembers `from` before a big move, dropping whatever was ahead of us in the list
+    pub fn record_jump(&mut self, from: Position) {
+        self.jumps.truncate(self.jump_index);
+        if self.jumps.last().is_some_and(|last| last.y == from.y) {
+            self.jumps.pop();
+        }
+        self.jumps.push(from);
+        if self.jumps.len() > MAX_JUMPS {
+            self.jumps.remove(0);
+        }
+        self.jump_index = self.jumps.len();
+    }
+
+    //the position before the last jump, `current` is remembered so forward can return to it
+    pub fn back(&mut self, current: Position) -> Option<Position> {
+        if self.jump_index == 0 {
+            return None;
+        }
+        if self.jump_index == self.jumps.len() {
+            self.jumps.push(current);
+        }
+        self.jump_index -= 1;
+        Some(self.jumps[self.jump_index])
+    }
+
+    pub fn forward(&mut self) -> Option<Position> {
+        if self.jump_index + 1 >= self.jumps.len() {
+            return None;
+        }
+        self.jump_index += 1;
+        Some(self.jumps[self.jump_index])
+    }
+
+    fn positions(&mut self) -> impl Iterator<Item = &mut Position> {
+        self.marks.values_mut().chain(self.jumps.iter_mut())
+    }
+
+    //`removed` lines from line `at` on were replaced by `inserted` new ones.
+    //Positions inside the replaced lines stay within what replaced them.
+    pub fn replace_lines(&mut self, at: usize, removed: usize, inserted: usize) {
+        for position in self.positions() {
+            if position.y < at {
+                continue;
+            }
+            if position.y >= at + removed {
+                position.y = position.y - removed + inserted;
+            } else if inserted == 0 {
+                position.y = at.saturating_sub(1);
+            } else {
+                position.y = at + (position.y - at).min(inserted - 1);
+            }
+        }
+    }
+
+    //line `at.y` was split at `at.x` and `lines` lines were put in between,
+    //`last_width` graphemes of them ending up in front of the rest of the split line
+    pub fn split_line(&mut self, at: Position, lines: usize, last_width: usize) {
+        for position in self.positions() {
+            if position.y > at.y {
+                position.y += lines;
+            } else if position.y == at.y && position.x >= at.x {
+                position.x = position.x - at.x + last_width;
+                position.y += lines;
+            }
+        }
+    }
+
+    //the lines after `at.y` up to `last` were joined onto it, what was at `last_x` on line
+    //`last` now being at `at.x`
+    pub fn join_lines(&mut self, at: Position, last: usize, last_x: usize) {
+        let removed = last - at.y;
+        for position in self.positions() {
+            if position.y > last {
+                position.y -= removed;
+            } else if position.y == last && position.x >= last_x {
+                position.x = position.x - last_x + at.x;
+                position.y = at.y;
+            } else if position.y > at.y {
+                *position = at;
+            }
+        }
+    }
+
+    //lines `from` moved to start at line `to`, the lines they passed moving the other way
+    pub fn move_lines(&mut self, from: std::ops::Range<usize>, to: usize) {
+        let (passed_start, passed_end) = if to < from.start {
+            (to, from.start)
+        } else {
+            (from.end, to + from.len())
+        };
+        for position in self.positions() {
+            if from.contains(&position.y) {
+                position.y = position.y - from.start + to;
+            } else if (passed_start..passed_end).contains(&position.y) {
+                if to < from.start {
+                    position.y += from.len();
+                } else {
+                    position.y -= from.len();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(x: usize, y: usize) -> Position {
+        Position { x, y }
+    }
+
+    #[test]
+    fn marks_follow_their_lines() {
+        let mut bookmarks = Bookmarks::default();
+        bookmarks.set('a', at(2, 5));
+        bookmarks.set('b', at(4, 3));
+        bookmarks.set('1', at(0, 1));
+
+        bookmarks.replace_lines(2, 0, 3);
+        assert_eq!(bookmarks.get('a'), Some(at(2, 8)));
+        assert_eq!(bookmarks.get('b'), Some(at(4, 6)));
+        assert_eq!(bookmarks.get('1'), Some(at(0, 1)));
+
+        bookmarks.replace_lines(5, 2, 0);
+        assert_eq!(bookmarks.get('b'), Some(at(4, 4)));
+        assert_eq!(bookmarks.get('a'), Some(at(2, 6)));
+
+        bookmarks.split_line(at(1, 6), 1, 0);
+        assert_eq!(bookmarks.get('a'), Some(at(1, 7)));
+        bookmarks.join_lines(at(5, 6), 7, 0);
+        assert_eq!(bookmarks.get('a'), Some(at(6, 6)));
+
+        bookmarks.move_lines(6..7, 4);
+        assert_eq!(bookmarks.get('a'), Some(at(6, 4)));
+        assert_eq!(bookmarks.get('b'), Some(at(4, 5)));
+        assert_eq!(bookmarks.on_line(5), Some('b'));
+        assert_eq!(bookmarks.names().collect::<String>(), "1ab");
+    }
+
+    #[test]
+    fn jumps_back_and_forward() {
+        let mut bookmarks = Bookmarks::default();
+        assert_eq!(bookmarks.back(at(0, 0)), None);
+        bookmarks.record_jump(at(0, 10));
+        bookmarks.record_jump(at(0, 20));
+        assert_eq!(bookmarks.back(at(0, 30)), Some(at(0, 20)));
+        assert_eq!(bookmarks.back(at(0, 20)), Some(at(0, 10)));
+        assert_eq!(bookmarks.back(at(0, 10)), None);
+        assert_eq!(bookmarks.forward(), Some(at(0, 20)));
+        assert_eq!(bookmarks.forward(), Some(at(0, 30)));
+        assert_eq!(bookmarks.forward(), None);
+
+        bookmarks.back(at(0, 30));
+        bookmarks.record_jump(at(0, 20));
+        assert_eq!(bookmarks.forward(), None);
+        assert_eq!(bookmarks.back(at(0, 40)), Some(at(0, 20)));
+        assert_eq!(bookmarks.back(at(0, 20)), Some(at(0, 10)));
+    }
+}
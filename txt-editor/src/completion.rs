@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+
+//how many candidates the popup shows at once
+pub const VISIBLE_CANDIDATES: usize = 8;
+
+pub struct Completion {
+    candidates: Vec<String>,
+    selected: usize,
+    //the start of the word, already typed
+    prefix: String,
+}
+
+impl Completion {
+    pub fn new(candidates: Vec<String>, prefix: &str) -> Self {
+        Self {
+            candidates,
+            selected: 0,
+            prefix: prefix.to_string(),
+        }
+    }
+
+    pub fn candidates(&self) -> &[String] {
+        &self.candidates
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1) % self.candidates.len();
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = self
+            .selected
+            .checked_sub(1)
+            .unwrap_or(self.candidates.len() - 1);
+    }
+
+    //what still needs typing to complete the selected word
+    pub fn remainder(&self) -> &str {
+        //every candidate starts with the prefix
+        &self.candidates[self.selected][self.prefix.len()..]
+    }
+}
+
+pub fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+//the word characters at the end of `before`, the text left of the cursor
+pub fn prefix_before(before: &str) -> &str {
+    let start = before
+        .char_indices()
+        .rev()
+        .find(|(_, c)| !is_word_char(*c))
+        .map_or(0, |(i, c)| i + c.len_utf8());
+    &before[start..]
+}
+
+struct Candidate {
+    //lines between the cursor and the closest occurrence in the current document
+    distance: usize,
+    frequency: usize,
+}
+
+//words starting with `prefix`, those close to the cursor first, then the most frequent ones.
+//Words that only appear in `others` come after everything found in `current`.
+pub fn rank(prefix: &str, cursor_line: usize, current: &[&str], others: &[&str]) -> Vec<String> {
+    if prefix.is_empty() {
+        return Vec::new();
+    }
+    let mut candidates: HashMap<&str, Candidate> = HashMap::new();
+    let lines = current
+        .iter()
+        .enumerate()
+        .map(|(index, line)| (Some(index), *line))
+        .chain(others.iter().map(|line| (None, *line)));
+    for (index, line) in lines {
+        for word in line.split(|c: char| !is_word_char(c)) {
+            if word.len() <= prefix.len() || !word.starts_with(prefix) {
+                continue;
+            }
+            let distance = index.map_or(usize::MAX, |index| index.abs_diff(cursor_line));
+            let candidate = candidates.entry(word).or_insert(Candidate {
+                distance,
+                frequency: 0,
+            });
+            candidate.distance = candidate.distance.min(distance);
+            candidate.frequency += 1;
+        }
+    }
+    let mut ranked: Vec<(&str, Candidate)> = candidates.into_iter().collect();
+    ranked.sort_by(|(a_word, a), (b_word, b)| {
+        a.distance
+            .cmp(&b.distance)
+            .then(b.frequency.cmp(&a.frequency))
+            .then(a_word.cmp(b_word))
+    });
+    ranked
+        .into_iter()
+        .map(|(word, _)| word.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranks_by_proximity_then_frequency() {
+        let current = [
+            "let counter = count_words(text);",
+            "",
+            "let count = counter + 1;",
+            "cou",
+        ];
+        let others = ["fn country() {}", "counter counter"];
+        assert_eq!(prefix_before("cou"), "cou");
+        assert_eq!(prefix_before("x.count_w"), "count_w");
+        assert_eq!(prefix_before("a "), "");
+        assert_eq!(
+            rank("cou", 3, &current, &others),
+            vec!["counter", "count", "count_words", "country"]
+        );
+        assert!(rank("", 0, &current, &others).is_empty());
+    }
+
+    #[test]
+    fn remainder_of_selected_word() {
+        let mut completion = Completion::new(vec!["über".to_string(), "übel".to_string()], "üb");
+        assert_eq!(completion.remainder(), "er");
+        completion.select_previous();
+        assert_eq!(completion.remainder(), "el");
+    }
+}
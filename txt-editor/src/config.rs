@@ -0,0 +1,46 @@
+use crate::storage;
+use std::collections::HashMap;
+
+const CONFIG_FILE: &str = "config";
+
+//settings from the `config` file in the editor's data directory, one `key = value` per line
+#[derive(Default)]
+pub struct Config {
+    values: HashMap<String, String>,
+}
+
+impl Config {
+    pub fn load() -> Self {
+        storage::read(CONFIG_FILE)
+            .map(|contents| Self::parse(&contents))
+            .unwrap_or_default()
+    }
+
+    fn parse(contents: &str) -> Self {
+        let values = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| line.split_once('='))
+            .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+            .collect();
+        Self { values }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_keys_and_values() {
+        let config =
+            Config::parse("# formatters\nformatter.rust = rustfmt --edition 2021\n\nbroken line\n");
+        assert_eq!(config.get("formatter.rust"), Some("rustfmt --edition 2021"));
+        assert_eq!(config.get("broken line"), None);
+    }
+}
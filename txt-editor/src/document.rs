@@ -1,26 +1,393 @@
+use std::collections::VecDeque;
 use std::fs;
-use std::io::Error;
-use std::io::Write;
+use std::io::{self, Error, ErrorKind};
+use std::io::{Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use crate::editorconfig::Settings;
+use crate::encoding::{self, Contents, Encoding};
+use crate::filetype::FileType;
+use crate::git::{GitDiff, LineChange};
+use crate::large_file::LargeFile;
+use crate::bookmarks::Bookmarks;
+use crate::completion;
+use crate::filter;
 use crate::Row;
 use crate::Position;
+use unicode_segmentation::UnicodeSegmentation;
+
+//files bigger than this are paged in from disk instead of being loaded whole
+const LARGE_FILE_SIZE: u64 = 64 * 1024 * 1024;
+//how many edits back undo can go
+const UNDO_LIMIT: usize = 100;
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+    Cr,
+}
+
+impl LineEnding {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Lf => "LF",
+            Self::CrLf => "CRLF",
+            Self::Cr => "CR",
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::CrLf => "\r\n",
+            Self::Cr => "\r",
+        }
+    }
+
+    //whichever line ending comes first, files mixing them are rare
+    fn detect(text: &str) -> Self {
+        match text.find(['\n', '\r']).map(|i| &text[i..]) {
+            Some(rest) if rest.starts_with("\r\n") => Self::CrLf,
+            Some(rest) if rest.starts_with('\r') => Self::Cr,
+            _ => Self::Lf,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Case {
+    Upper,
+    Lower,
+    //first letter of every word upper case, the rest lower case
+    Title,
+}
+
+//what an edit changed: the rows it replaced from `start` on and how many rows it left in their
+//place, with where the cursor was before it
+struct Snapshot {
+    start: usize,
+    rows: Vec<Row>,
+    len: usize,
+    cursor: Position,
+    bookmarks: Bookmarks,
+}
+
 #[derive(Default)]
 pub struct Document {
     rows: Vec<Row>,
-    pub file_name: Option<String>
+    pub file_name: Option<String>,
+    //bumped on every edit so cached views of the rows know when to recompute
+    revision: usize,
+    //the revision last written to disk, anything else means unsaved changes
+    saved_revision: usize,
+    git: Option<GitDiff>,
+    read_only: bool,
+    //set when the document is a listing of this directory rather than a file
+    directory: Option<PathBuf>,
+    encoding: Encoding,
+    line_ending: LineEnding,
+    //from .editorconfig files, applied when saving
+    settings: Settings,
+    //binary files are shown as a read-only hex dump
+    binary: bool,
+    //for large files `rows` only holds the lines from `window_start` on that are near the screen
+    large: Option<LargeFile>,
+    window_start: usize,
+    undo_stack: VecDeque<Snapshot>,
+    redo_stack: Vec<Snapshot>,
+    //the rows as of the last checkpoint, undo or redo, compared with the rows to find what changed
+    undo_base: Option<Vec<Row>>,
+    //cursor and bookmarks at the last checkpoint, until the edit after it is recorded
+    undo_open: Option<(Position, Bookmarks)>,
+    bookmarks: Bookmarks,
 }
 
 impl Document {
     pub fn open(filename: &str) -> Result<Self, std::io::Error> {
-        let contents = fs::read_to_string(filename)?;
+        if fs::metadata(filename)?.len() > LARGE_FILE_SIZE {
+            return Self::open_large(filename);
+        }
+        let bytes = fs::read(filename)?;
+        let mut document = Self::from_bytes(&bytes);
+        document.file_name = Some(filename.to_string());
+        if !document.binary {
+            document.git = GitDiff::load(filename);
+            document.settings = Settings::for_file(Path::new(filename));
+        }
+        Ok(document)
+    }
+
+    //an unnamed document holding whatever was piped into the editor
+    pub fn from_stdin() -> Result<Self, Error> {
+        let mut bytes = Vec::new();
+        io::stdin().read_to_end(&mut bytes)?;
+        Ok(Self::from_bytes(&bytes))
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Self {
         let mut rows = Vec::new();
-        for value in contents.lines() {
-            rows.push(Row::from(value));
+        let mut line_ending = LineEnding::default();
+        let (encoding, binary) = match encoding::decode(bytes) {
+            Contents::Text(contents, encoding) => {
+                line_ending = LineEnding::detect(&contents);
+                if line_ending == LineEnding::Cr {
+                    let contents = contents.strip_suffix('\r').unwrap_or(&contents);
+                    rows.extend(contents.split('\r').map(Row::from));
+                } else {
+                    rows.extend(contents.lines().map(Row::from));
+                }
+                (encoding, false)
+            }
+            Contents::Binary => {
+                for value in encoding::hex_dump(bytes) {
+                    rows.push(Row::from(&value[..]));
+                }
+                (Encoding::default(), true)
+            }
+        };
+
+        Self {
+            rows,
+            read_only: binary,
+            encoding,
+            line_ending,
+            binary,
+            ..Self::default()
+        }
+    }
+
+    //read-only view of a file too big to load, lines are indexed in the background
+    fn open_large(filename: &str) -> Result<Self, Error> {
+        Ok(Self {
+            rows: Vec::new(),
+            file_name: Some(filename.to_string()),
+            revision: 0,
+            git: None,
+            read_only: true,
+            directory: None,
+            encoding: Encoding::default(),
+            binary: false,
+            large: Some(LargeFile::open(Path::new(filename))?),
+            ..Self::default()
+        })
+    }
+
+    pub fn is_large(&self) -> bool {
+        self.large.is_some()
+    }
+
+    //whether a large file is still being indexed, in which case `len` is not final yet
+    pub fn is_indexing(&self) -> bool {
+        self.large
+            .as_ref()
+            .is_some_and(|large| !large.line_count().1)
+    }
+
+    //makes sure rows `start..start + count` of a large file are in memory
+    pub fn page_in(&mut self, start: usize, count: usize) {
+        let large = match &self.large {
+            Some(large) => large,
+            None => return,
+        };
+        let end = start.saturating_add(count).min(large.line_count().0.max(start + 1));
+        if start >= self.window_start && end <= self.window_start + self.rows.len() {
+            return;
         }
+        if let Ok(lines) = large.read_lines(start, count) {
+            self.rows = lines.iter().map(|line| Row::from(&line[..])).collect();
+            self.window_start = start;
+        }
+    }
 
-        Ok(Self { rows, file_name: Some(filename.to_string()) })
+    //lists the entries of a directory one per row, sub directories first and marked with a trailing slash
+    pub fn open_directory(path: &str) -> Result<Self, Error> {
+        let mut directories = Vec::new();
+        let mut files = Vec::new();
+        for entry in fs::read_dir(path)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if entry.file_type()?.is_dir() {
+                directories.push(format!("{}/", name));
+            } else {
+                files.push(name);
+            }
+        }
+        directories.sort();
+        files.sort();
+        let mut rows = vec![Row::from("../")];
+        rows.extend(directories.iter().chain(files.iter()).map(|name| Row::from(&name[..])));
+
+        Ok(Self {
+            rows,
+            file_name: Some(path.to_string()),
+            revision: 0,
+            git: None,
+            read_only: true,
+            directory: Some(PathBuf::from(path)),
+            ..Self::default()
+        })
+    }
+
+    //names a document that had none, the .editorconfig settings for its new place apply
+    pub fn set_file_name(&mut self, file_name: &str) {
+        self.file_name = Some(file_name.to_string());
+        self.settings = Settings::for_file(Path::new(file_name));
+    }
+
+    pub fn set_read_only(&mut self, read_only: bool) {
+        //binary dumps and large files can never be edited
+        self.read_only = read_only || self.binary || self.large.is_some() || self.directory.is_some();
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    pub fn file_type(&self) -> FileType {
+        match &self.file_name {
+            Some(file_name) => FileType::from(file_name),
+            None => FileType::default(),
+        }
+    }
+
+    //remembers the current contents so the next edit can be undone
+    pub fn checkpoint(&mut self, cursor: &Position) {
+        if self.read_only {
+            return;
+        }
+        self.record_changes(cursor);
+        self.undo_open = Some((*cursor, self.bookmarks.clone()));
+        self.redo_stack.clear();
+    }
+
+    //goes back to the last checkpoint, returns where the cursor was then
+    pub fn undo(&mut self, cursor: &Position) -> Option<Position> {
+        self.record_changes(cursor);
+        let snapshot = self.undo_stack.pop_back()?;
+        Some(self.restore(snapshot, cursor, true))
+    }
+
+    pub fn redo(&mut self, cursor: &Position) -> Option<Position> {
+        self.record_changes(cursor);
+        let snapshot = self.redo_stack.pop()?;
+        Some(self.restore(snapshot, cursor, false))
+    }
+
+    //turns the rows changed since the last checkpoint into an undo step. Changes made without one,
+    //like trimming on save, are a step of their own and leave nothing to redo.
+    fn record_changes(&mut self, cursor: &Position) {
+        let base = match &mut self.undo_base {
+            Some(base) => base,
+            None => {
+                self.undo_base = Some(self.rows.clone());
+                return;
+            }
+        };
+        let same = |(old, new): (&Row, &Row)| old.as_str() == new.as_str();
+        let prefix = base.iter().zip(&self.rows).take_while(|&rows| same(rows)).count();
+        let suffix = base[prefix..]
+            .iter()
+            .rev()
+            .zip(self.rows[prefix..].iter().rev())
+            .take_while(|&rows| same(rows))
+            .count();
+        let replaced = prefix..base.len() - suffix;
+        let changed = prefix..self.rows.len() - suffix;
+        let open = self.undo_open.take();
+        if open.is_none() && replaced.is_empty() && changed.is_empty() {
+            return;
+        }
+        let rows: Vec<Row> = base
+            .splice(replaced, self.rows[changed.clone()].iter().cloned())
+            .collect();
+        let (cursor, bookmarks) = match open {
+            Some(open) => open,
+            None => {
+                self.redo_stack.clear();
+                (*cursor, self.bookmarks.clone())
+            }
+        };
+        self.undo_stack.push_back(Snapshot {
+            start: prefix,
+            rows,
+            len: changed.len(),
+            cursor,
+            bookmarks,
+        });
+        if self.undo_stack.len() > UNDO_LIMIT {
+            self.undo_stack.pop_front();
+        }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot, cursor: &Position, undoing: bool) -> Position {
+        let changed = snapshot.start..snapshot.start + snapshot.len;
+        if let Some(base) = &mut self.undo_base {
+            base.splice(changed.clone(), snapshot.rows.iter().cloned());
+        }
+        let len = snapshot.rows.len();
+        let current = Snapshot {
+            start: snapshot.start,
+            rows: self.rows.splice(changed, snapshot.rows).collect(),
+            len,
+            cursor: *cursor,
+            bookmarks: std::mem::replace(&mut self.bookmarks, snapshot.bookmarks),
+        };
+        if undoing {
+            self.redo_stack.push(current);
+        } else {
+            self.undo_stack.push_back(current);
+        }
+        self.revision += 1;
+        snapshot.cursor
+    }
+
+    //the rows in `range` as text, each followed by a newline
+    pub fn text(&self, range: Range<usize>) -> String {
+        let mut text = String::new();
+        for row in self.rows.get(range).unwrap_or_default() {
+            text.push_str(row.as_str());
+            text.push('\n');
+        }
+        text
+    }
+
+    pub fn is_binary(&self) -> bool {
+        self.binary
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    pub fn is_directory(&self) -> bool {
+        self.directory.is_some()
+    }
+
+    //path of the entry listed on row `index` of a directory listing
+    pub fn directory_entry(&self, index: usize) -> Option<PathBuf> {
+        let directory = self.directory.as_ref()?;
+        let name = self.rows.get(index)?.as_str().trim_end_matches('/');
+        Some(directory.join(name))
+    }
+
+    //whether this document was loaded from `path`, however the two paths are spelled
+    pub fn is_file(&self, path: &Path) -> bool {
+        match &self.file_name {
+            Some(file_name) => match (fs::canonicalize(file_name), fs::canonicalize(path)) {
+                (Ok(a), Ok(b)) => a == b,
+                _ => Path::new(file_name) == path,
+            },
+            None => false,
+        }
     }
 
     pub fn insert(&mut self, at: &Position, c:char){
+        if self.read_only {
+            return;
+        }
+        self.revision += 1;
         if c == '\n' {
             self.insert_new_line(at);
             return;
@@ -36,9 +403,10 @@ impl Document {
     }
 
     pub fn insert_new_line(&mut self, at: &Position){
-        if at.y > self.len(){
+        if at.y > self.len() || self.read_only {
             return
         }
+        self.revision += 1;
         if at.y == self.len() {
             self.rows.push(Row::default());
             return;
@@ -46,43 +414,764 @@ impl Document {
 
         let new_row = self.rows.get_mut(at.y).unwrap().split(at.x);
         self.rows.insert(at.y + 1, new_row);
+        self.bookmarks.split_line(*at, 1, 0);
     }
 
     pub fn delete(&mut self, at: &Position){
         let len = self.len();
-        if at.y >= len{
+        if at.y >= len || self.read_only {
             return;
         }
+        self.revision += 1;
         if at.x == self.rows.get_mut(at.y).unwrap().len() && at.y < len -1 {
             let next_row = self.rows.remove(at.y + 1);
             let row = self.rows.get_mut(at.y).unwrap();
             row.append(&next_row);
+            self.bookmarks.join_lines(*at, at.y + 1, 0);
         }else{
             let row = self.rows.get_mut(at.y).unwrap();
             row.delete(at.x);
         }
     }
     
-    pub fn save(&self)->Result<(), Error>{
+    //.editorconfig settings win over the line ending and encoding the file was read with
+    pub fn save(&mut self)->Result<(), Error>{
+        if self.read_only {
+            return Err(Error::new(ErrorKind::PermissionDenied, "document is read-only"));
+        }
+        //piped in text has nowhere to go until it is given a name
+        if self.file_name.is_none() {
+            return Err(Error::new(ErrorKind::InvalidInput, "document has no file name"));
+        }
+        if self.settings.trim_trailing_whitespace == Some(true) {
+            self.trim_trailing_whitespace();
+        }
+        self.line_ending = self.settings.end_of_line.unwrap_or(self.line_ending);
+        self.encoding = self.settings.charset.unwrap_or(self.encoding);
         if let Some(file_name) = &self.file_name{
-            let mut file = fs::File::create(file_name)?;
-            for row in &self.rows{
-                file.write_all(row.as_bytes())?;
-                file.write_all(b"\n");
+            let line_ending = self.line_ending.as_str();
+            let mut contents = self.lines().collect::<Vec<_>>().join(line_ending);
+            if !self.rows.is_empty() && self.settings.insert_final_newline != Some(false) {
+                contents.push_str(line_ending);
             }
+            let bytes = self.encoding.encode(&contents).map_err(|c| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("'{}' cannot be saved as {}", c, self.encoding.name()),
+                )
+            })?;
+            let mut file = fs::File::create(file_name)?;
+            file.write_all(&bytes)?;
+            self.saved_revision = self.revision;
         };
         Ok(())
     }
 
+    pub fn trim_trailing_whitespace(&mut self) {
+        for row in &mut self.rows {
+            if let Some(start) = row.trailing_whitespace_start() {
+                row.delete_range(start, row.len());
+                self.revision += 1;
+            }
+        }
+    }
+
+    pub fn bookmarks(&self) -> &Bookmarks {
+        &self.bookmarks
+    }
+
+    pub fn bookmarks_mut(&mut self) -> &mut Bookmarks {
+        &mut self.bookmarks
+    }
+
+    pub fn is_modified(&self) -> bool {
+        self.revision != self.saved_revision
+    }
+
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    //swaps the rows in `range` for `lines`, returns the range the new lines occupy
+    pub fn replace_rows(&mut self, range: Range<usize>, lines: &[String]) -> Range<usize> {
+        let start = range.start.min(self.len());
+        let end = range.end.min(self.len());
+        if self.read_only {
+            return start..start;
+        }
+        self.rows
+            .splice(start..end, lines.iter().map(|line| Row::from(&line[..])));
+        self.revision += 1;
+        self.bookmarks.replace_lines(start, end - start, lines.len());
+        start..start + lines.len()
+    }
+
+    //swaps rows `lines` for the output of `command` fed with them, as a single undoable edit
+    //with the cursor at `cursor` before it. The rows the output took, None when the command
+    //left them as they were.
+    pub fn filter_rows(
+        &mut self,
+        command: &str,
+        lines: Range<usize>,
+        cursor: &Position,
+    ) -> Result<Option<Range<usize>>, String> {
+        let input = self.text(lines.clone());
+        let output = filter::run(command, &input)?;
+        if output == input {
+            return Ok(None);
+        }
+        self.checkpoint(cursor);
+        let output: Vec<String> = output.lines().map(String::from).collect();
+        Ok(Some(self.replace_rows(lines, &output)))
+    }
+
+    //the text from `start` up to `end`, lines joined by newlines
+    pub fn text_between(&self, start: &Position, end: &Position) -> String {
+        let mut text = String::new();
+        for y in start.y..=end.y {
+            let row = match self.rows.get(y) {
+                Some(row) => row,
+                None => break,
+            };
+            let from = if y == start.y { start.x } else { 0 };
+            let to = if y == end.y { end.x } else { row.len() };
+            text.push_str(&row.substring(from, to));
+            if y < end.y {
+                text.push('\n');
+            }
+        }
+        text
+    }
+
+    pub fn delete_between(&mut self, start: &Position, end: &Position) {
+        if self.read_only || start.y >= self.len() {
+            return;
+        }
+        self.revision += 1;
+        let end_y = end.y.min(self.len() - 1);
+        let tail = self.rows[end_y].substring(end.x, usize::MAX);
+        let row = &mut self.rows[start.y];
+        row.delete_range(start.x, usize::MAX);
+        row.insert_str(start.x, &tail);
+        self.rows.drain(start.y + 1..end_y + 1);
+        if end_y > start.y {
+            self.bookmarks.join_lines(*start, end_y, end.x);
+        }
+    }
+
+    //inserts text that may span lines, returns the position right after it
+    pub fn insert_text(&mut self, at: &Position, text: &str) -> Position {
+        if self.read_only || at.y > self.len() {
+            return *at;
+        }
+        self.revision += 1;
+        if at.y == self.len() {
+            self.rows.push(Row::default());
+        }
+        let tail = self.rows[at.y].split(at.x);
+        let mut y = at.y;
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                y += 1;
+                self.rows.insert(y, Row::default());
+            }
+            let row = &mut self.rows[y];
+            row.insert_str(row.len(), line);
+        }
+        let x = self.rows[y].len();
+        self.rows[y].append(&tail);
+        if y > at.y {
+            self.bookmarks.split_line(*at, y - at.y, x);
+        }
+        Position { x, y }
+    }
+
+    //the part of each of `lines` between screen columns `left` and `right`
+    pub fn block_text(&self, lines: Range<usize>, left: usize, right: usize) -> Vec<String> {
+        lines
+            .filter_map(|y| self.rows.get(y))
+            .map(|row| row.substring(row.index_at_column(left), row.index_at_column(right)))
+            .collect()
+    }
+
+    pub fn delete_block(&mut self, lines: Range<usize>, left: usize, right: usize) {
+        if self.read_only {
+            return;
+        }
+        self.revision += 1;
+        for y in lines {
+            if let Some(row) = self.rows.get_mut(y) {
+                let start = row.index_at_column(left);
+                let end = row.index_at_column(right);
+                row.delete_range(start, end);
+            }
+        }
+    }
+
+    //puts `texts` at screen column `column` of consecutive lines starting with `first_line`,
+    //padding short lines with spaces and adding lines past the end of the document
+    pub fn insert_block(&mut self, first_line: usize, column: usize, texts: &[String]) {
+        if self.read_only || first_line > self.len() {
+            return;
+        }
+        self.revision += 1;
+        for (y, text) in (first_line..).zip(texts) {
+            if y == self.len() {
+                self.rows.push(Row::default());
+            }
+            let row = &mut self.rows[y];
+            row.pad_to_column(column);
+            let at = row.index_at_column(column);
+            row.insert_str(at, text);
+        }
+    }
+
+    //the lines clamped to the document, or None if there is nothing to work on
+    fn editable_lines(&self, lines: Range<usize>) -> Option<Range<usize>> {
+        let lines = lines.start.min(self.rows.len())..lines.end.min(self.rows.len());
+        if self.read_only || lines.is_empty() {
+            None
+        } else {
+            Some(lines)
+        }
+    }
+
+    //puts a copy of the lines right below them, returns where the copy is
+    pub fn duplicate_lines(&mut self, lines: Range<usize>) -> Option<Range<usize>> {
+        let lines = self.editable_lines(lines)?;
+        self.revision += 1;
+        let copy: Vec<Row> = self.rows[lines.clone()].to_vec();
+        self.rows.splice(lines.end..lines.end, copy);
+        self.bookmarks.replace_lines(lines.end, 0, lines.len());
+        Some(lines.end..lines.end + lines.len())
+    }
+
+    //swaps the lines with the one above or below them, returns where they ended up
+    pub fn move_lines(&mut self, lines: Range<usize>, up: bool) -> Option<Range<usize>> {
+        let lines = self.editable_lines(lines)?;
+        if up && lines.start == 0 || !up && lines.end == self.rows.len() {
+            return None;
+        }
+        self.revision += 1;
+        let moved = if up {
+            self.rows[lines.start - 1..lines.end].rotate_left(1);
+            lines.start - 1..lines.end - 1
+        } else {
+            self.rows[lines.start..lines.end + 1].rotate_right(1);
+            lines.start + 1..lines.end + 1
+        };
+        self.bookmarks.move_lines(lines, moved.start);
+        Some(moved)
+    }
+
+    //joins the lines into one, or a single line with the next, separated by a space with the
+    //leading whitespace of the joined lines dropped. Returns the column of the last join.
+    pub fn join_lines(&mut self, lines: Range<usize>) -> Option<usize> {
+        let lines = self.editable_lines(lines)?;
+        let end = lines.end.max(lines.start + 2).min(self.rows.len());
+        if end - lines.start < 2 {
+            return None;
+        }
+        self.revision += 1;
+        let mut column = 0;
+        let joined: Vec<Row> = self.rows.drain(lines.start + 1..end).collect();
+        let row = &mut self.rows[lines.start];
+        for next in joined {
+            let text = next.as_str().trim_start();
+            let trimmed = row.as_str().trim_end().to_string();
+            *row = Row::from(&trimmed[..]);
+            column = row.len();
+            if !text.is_empty() && !row.is_empty() {
+                row.insert(column, ' ');
+                column += 1;
+            }
+            row.insert_str(column, text);
+            //the joined lines were taken out at once, but for the bookmarks each one is next in turn
+            let indentation = next.len() - Row::from(text).len();
+            let at = Position { x: column, y: lines.start };
+            self.bookmarks.join_lines(at, lines.start + 1, indentation);
+        }
+        Some(column)
+    }
+
+    pub fn sort_lines(&mut self, lines: Range<usize>) {
+        if let Some(lines) = self.editable_lines(lines) {
+            self.revision += 1;
+            self.rows[lines].sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        }
+    }
+
+    //deletes from `at` to the end of its line, or joins the next line when already at the end
+    pub fn delete_to_end_of_line(&mut self, at: &Position) {
+        let len = match self.rows.get(at.y) {
+            Some(row) => row.len(),
+            None => return,
+        };
+        if at.x >= len {
+            self.delete(at);
+        } else if !self.read_only {
+            self.revision += 1;
+            self.rows[at.y].delete_range(at.x, len);
+        }
+    }
+
+    //converts the text from `start` to `end`, returns where the converted text ends
+    //since a conversion can change its length, e.g. ß becoming SS
+    pub fn convert_case(&mut self, start: &Position, end: &Position, case: Case) -> Position {
+        let text = self.text_between(start, end);
+        let converted = match case {
+            Case::Upper => text.to_uppercase(),
+            Case::Lower => text.to_lowercase(),
+            Case::Title => title_case(&text),
+        };
+        if self.read_only || converted == text {
+            return *end;
+        }
+        self.delete_between(start, end);
+        self.insert_text(start, &converted)
+    }
+
+    //start and end of the word the cursor is in or right after
+    pub fn word_at(&self, at: &Position) -> Option<(Position, Position)> {
+        let row = self.rows.get(at.y)?;
+        let graphemes: Vec<&str> = row.as_str().graphemes(true).collect();
+        let is_word = |index: usize| {
+            graphemes
+                .get(index)
+                .is_some_and(|grapheme| grapheme.chars().all(completion::is_word_char))
+        };
+        let mut start = at.x.min(graphemes.len());
+        if !is_word(start) && start > 0 && is_word(start - 1) {
+            start -= 1;
+        }
+        if !is_word(start) {
+            return None;
+        }
+        let mut end = start;
+        while start > 0 && is_word(start - 1) {
+            start -= 1;
+        }
+        while is_word(end) {
+            end += 1;
+        }
+        Some((Position { x: start, y: at.y }, Position { x: end, y: at.y }))
+    }
+
+    //the text of the rows in memory, which for a large file is just the window near the screen
+    pub fn lines(&self) -> impl Iterator<Item = &str> {
+        self.rows.iter().map(Row::as_str)
+    }
+
+    //brings the diff markers up to date with the current contents
+    pub fn update_git_changes(&mut self) {
+        if let Some(git) = &mut self.git {
+            let lines: Vec<&str> = self.rows.iter().map(Row::as_str).collect();
+            git.update(&lines, self.revision);
+        }
+    }
+
+    pub fn has_git(&self) -> bool {
+        self.git.is_some()
+    }
+
+    pub fn git_change(&self, index: usize) -> Option<LineChange> {
+        self.git.as_ref().and_then(|git| git.change_at(index))
+    }
+
+    pub fn next_hunk(&self, index: usize) -> Option<usize> {
+        let git = self.git.as_ref()?;
+        git.next_hunk(index).map(|hunk| hunk.new.start)
+    }
+
+    pub fn previous_hunk(&self, index: usize) -> Option<usize> {
+        let git = self.git.as_ref()?;
+        git.previous_hunk(index).map(|hunk| hunk.new.start)
+    }
+
+    //restores the index version of the hunk under `index`, returns the first line of the restored hunk
+    pub fn revert_hunk(&mut self, index: usize) -> Option<usize> {
+        let git = self.git.as_mut()?;
+        let lines: Vec<&str> = self.rows.iter().map(Row::as_str).collect();
+        git.update_now(&lines, self.revision);
+        let hunk = git.hunk_at(index)?.clone();
+        let lines = git.base_lines(&hunk).to_vec();
+        Some(self.replace_rows(hunk.new, &lines).start)
+    }
+
+    //position of the next (or previous) occurrence of `query` from `at`
+    pub fn find(&self, query: &str, at: &Position, forward: bool) -> Option<Position> {
+        if let Some(large) = &self.large {
+            return Self::find_large(large, query, at, forward);
+        }
+        if forward {
+            for y in at.y..self.len() {
+                let x = if y == at.y { at.x } else { 0 };
+                if let Some(x) = self.rows[y].find(query, x, true) {
+                    return Some(Position { x, y });
+                }
+            }
+        } else {
+            for y in (0..=at.y.min(self.len().saturating_sub(1))).rev() {
+                let row = self.rows.get(y)?;
+                let x = if y == at.y { at.x } else { row.len() };
+                if let Some(x) = row.find(query, x, false) {
+                    return Some(Position { x, y });
+                }
+            }
+        }
+        None
+    }
+
+    //same as `find`, but reading the lines from disk as it goes
+    fn find_large(large: &LargeFile, query: &str, at: &Position, forward: bool) -> Option<Position> {
+        let row = |y: usize| -> Option<Row> {
+            let lines = large.read_lines(y, 1).ok()?;
+            lines.first().map(|line| Row::from(&line[..]))
+        };
+        if let Some(x) = row(at.y).and_then(|current| current.find(query, at.x, forward)) {
+            return Some(Position { x, y: at.y });
+        }
+        let y = if forward {
+            large.find_forward(query, at.y + 1).ok()??
+        } else {
+            large.find_backward(query, at.y).ok()??
+        };
+        let row = row(y)?;
+        let x = if forward {
+            row.find(query, 0, true)
+        } else {
+            row.find(query, row.len(), false)
+        }?;
+        Some(Position { x, y })
+    }
+
     pub fn row(&self, index: usize) -> Option<&Row> {
+        if self.large.is_some() {
+            return self.rows.get(index.checked_sub(self.window_start)?);
+        }
         self.rows.get(index)
     }
 
     pub fn is_empty(&self) -> bool {
-        self.rows.is_empty()
+        self.len() == 0
     }
 
     pub fn len(&self)->usize{
-        self.rows.len()
+        match &self.large {
+            Some(large) => large.line_count().0,
+            None => self.rows.len(),
+        }
+    }
+}
+
+fn title_case(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut in_word = false;
+    for c in text.chars() {
+        if in_word {
+            result.extend(c.to_lowercase());
+        } else {
+            result.extend(c.to_uppercase());
+        }
+        in_word = c.is_alphanumeric() || c == '\'';
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document(text: &str) -> Document {
+        Document::from_bytes(text.as_bytes())
+    }
+
+    fn lines(document: &Document) -> Vec<&str> {
+        document.lines().collect()
+    }
+
+    //a file with `bytes` in a directory of its own, which the test removes
+    fn temp_file(dir: &str, name: &str, bytes: &[u8]) -> (PathBuf, String) {
+        let dir = std::env::temp_dir().join(format!("txt-editor-{}-{}", dir, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join(name);
+        fs::write(&file, bytes).unwrap();
+        let file = file.to_string_lossy().to_string();
+        (dir, file)
+    }
+
+    #[test]
+    fn duplicates_and_moves_lines() {
+        let mut doc = document("a\nb\nc\n");
+        assert_eq!(doc.duplicate_lines(0..2), Some(2..4));
+        assert_eq!(lines(&doc), ["a", "b", "a", "b", "c"]);
+        assert_eq!(doc.move_lines(3..5, true), Some(2..4));
+        assert_eq!(lines(&doc), ["a", "b", "b", "c", "a"]);
+        assert_eq!(doc.move_lines(0..1, true), None);
+        assert_eq!(doc.move_lines(0..1, false), Some(1..2));
+        assert_eq!(lines(&doc), ["b", "a", "b", "c", "a"]);
+        assert_eq!(doc.move_lines(4..5, false), None);
+    }
+
+    #[test]
+    fn joins_sorts_and_deletes_to_end_of_line() {
+        let mut doc = document("fn main() {  \n    body\n\n}\nz\ny\nx\n");
+        assert_eq!(doc.join_lines(0..1), Some(12));
+        assert_eq!(lines(&doc)[0], "fn main() { body");
+        assert_eq!(doc.join_lines(0..3), Some(17));
+        assert_eq!(lines(&doc), ["fn main() { body }", "z", "y", "x"]);
+        assert_eq!(doc.join_lines(3..4), None);
+
+        doc.sort_lines(1..4);
+        assert_eq!(lines(&doc), ["fn main() { body }", "x", "y", "z"]);
+
+        doc.delete_to_end_of_line(&Position { x: 11, y: 0 });
+        assert_eq!(lines(&doc)[0], "fn main() {");
+        doc.delete_to_end_of_line(&Position { x: 11, y: 0 });
+        assert_eq!(lines(&doc), ["fn main() {x", "y", "z"]);
+    }
+
+    #[test]
+    fn converts_case_of_ranges_and_words() {
+        let mut doc = document("straße and o'neil\nwORLD\n");
+        let start = Position { x: 0, y: 0 };
+        let end = doc.convert_case(&start, &Position { x: 6, y: 0 }, Case::Upper);
+        assert_eq!(end, Position { x: 7, y: 0 });
+        assert_eq!(lines(&doc)[0], "STRASSE and o'neil");
+        doc.convert_case(&Position { x: 8, y: 0 }, &Position { x: 5, y: 1 }, Case::Title);
+        assert_eq!(lines(&doc), ["STRASSE And O'neil", "World"]);
+        doc.convert_case(&start, &Position { x: 5, y: 1 }, Case::Lower);
+        assert_eq!(lines(&doc), ["strasse and o'neil", "world"]);
+
+        let word = (Position { x: 8, y: 0 }, Position { x: 11, y: 0 });
+        assert_eq!(doc.word_at(&Position { x: 9, y: 0 }), Some(word));
+        assert_eq!(doc.word_at(&Position { x: 11, y: 0 }), Some(word));
+        let first = (start, Position { x: 7, y: 0 });
+        assert_eq!(doc.word_at(&Position { x: 7, y: 0 }), Some(first));
+        let last = (Position { x: 0, y: 1 }, Position { x: 5, y: 1 });
+        assert_eq!(doc.word_at(&Position { x: 5, y: 1 }), Some(last));
+    }
+
+    #[test]
+    fn undo_keeps_only_the_changed_rows() {
+        let mut doc = document("a\nb\nc\nd\n");
+        let at = |x, y| Position { x, y };
+        doc.checkpoint(&at(0, 1));
+        doc.insert(&at(1, 1), '!');
+        doc.checkpoint(&at(2, 1));
+        doc.delete_between(&at(0, 2), &at(0, 3));
+        doc.checkpoint(&at(0, 2));
+        doc.insert_text(&at(0, 0), "x\ny");
+        assert_eq!(lines(&doc), ["x", "ya", "b!", "d"]);
+
+        assert_eq!(doc.undo(&at(1, 1)), Some(at(0, 2)));
+        assert_eq!(lines(&doc), ["a", "b!", "d"]);
+        //only the rows an edit touched are kept for undoing it
+        let step = doc.undo_stack.back().unwrap();
+        assert_eq!((step.start, step.rows.len(), step.len), (2, 1, 0));
+        assert_eq!(doc.undo(&at(0, 2)), Some(at(2, 1)));
+        assert_eq!(lines(&doc), ["a", "b!", "c", "d"]);
+        assert_eq!(doc.undo(&at(2, 1)), Some(at(0, 1)));
+        assert_eq!(lines(&doc), ["a", "b", "c", "d"]);
+        assert_eq!(doc.undo(&at(0, 1)), None);
+
+        assert_eq!(doc.redo(&at(0, 1)), Some(at(2, 1)));
+        assert_eq!(doc.redo(&at(2, 1)), Some(at(0, 2)));
+        assert_eq!(lines(&doc), ["a", "b!", "d"]);
+        //an edit made without a checkpoint is undone on its own, and redo has nothing left
+        doc.insert(&at(1, 2), '?');
+        assert_eq!(doc.redo(&at(0, 2)), None);
+        assert_eq!(doc.undo(&at(2, 2)), Some(at(0, 2)));
+        assert_eq!(lines(&doc), ["a", "b!", "d"]);
+
+        for i in 0..UNDO_LIMIT + 10 {
+            doc.checkpoint(&at(0, 0));
+            doc.insert(&at(0, 0), char::from(b'a' + (i % 26) as u8));
+        }
+        assert_eq!(doc.undo_stack.len(), UNDO_LIMIT);
+    }
+
+    #[test]
+    fn saves_in_the_encoding_and_line_endings_it_opened_with() {
+        let (dir, latin1) = temp_file("encoding", "latin1.txt", b"caf\xE9\r\nna\xEFve\r\n");
+        let mut doc = Document::open(&latin1).unwrap();
+        assert_eq!(lines(&doc), ["café", "naïve"]);
+        assert_eq!((doc.encoding(), doc.line_ending()), (Encoding::Latin1, LineEnding::CrLf));
+        doc.insert(&Position { x: 4, y: 0 }, 's');
+        doc.save().unwrap();
+        assert_eq!(fs::read(&latin1).unwrap(), b"caf\xE9s\r\nna\xEFve\r\n");
+        //what the encoding cannot hold is refused, leaving the file as it was
+        doc.insert(&Position { x: 0, y: 1 }, '€');
+        assert_eq!(doc.save().unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(fs::read(&latin1).unwrap(), b"caf\xE9s\r\nna\xEFve\r\n");
+
+        let utf16 = dir.join("utf16.txt").to_string_lossy().to_string();
+        fs::write(&utf16, b"\xFF\xFEh\0i\0\n\0").unwrap();
+        let mut doc = Document::open(&utf16).unwrap();
+        assert_eq!(doc.encoding(), Encoding::Utf16Le { bom: true });
+        doc.insert(&Position { x: 2, y: 0 }, '!');
+        doc.save().unwrap();
+        assert_eq!(fs::read(&utf16).unwrap(), b"\xFF\xFEh\0i\0!\0\n\0");
+
+        let binary = dir.join("binary.bin").to_string_lossy().to_string();
+        fs::write(&binary, b"\x7FELF\x02\x01\x01\0\0\0").unwrap();
+        let mut doc = Document::open(&binary).unwrap();
+        assert!(doc.is_binary() && doc.is_read_only());
+        assert_eq!(doc.save().unwrap_err().kind(), ErrorKind::PermissionDenied);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn large_files_page_in_windows_and_stay_read_only() {
+        let contents: String = (0..5000).map(|i| format!("line {}\n", i)).collect();
+        let (dir, file) = temp_file("large", "big.log", contents.as_bytes());
+        let mut doc = Document::open_large(&file).unwrap();
+        assert!(doc.is_large() && doc.is_read_only());
+        while doc.is_indexing() {
+            std::thread::yield_now();
+        }
+        assert_eq!(doc.len(), 5000);
+
+        //rows are addressed by their line in the file, only the window is in memory
+        doc.page_in(4000, 50);
+        assert_eq!(doc.row(4000).map(Row::as_str), Some("line 4000"));
+        assert_eq!(doc.row(4049).map(Row::as_str), Some("line 4049"));
+        assert!(doc.row(3999).is_none() && doc.row(4050).is_none());
+        assert_eq!(lines(&doc).len(), 50);
+        doc.page_in(4010, 20);
+        assert_eq!(doc.row(4000).map(Row::as_str), Some("line 4000"));
+        let found = doc.find("line 1234", &Position { x: 0, y: 4000 }, false);
+        assert_eq!(found, Some(Position { x: 0, y: 1234 }));
+
+        //once indexed it is still paged from disk, never edited
+        doc.set_read_only(false);
+        assert!(doc.is_read_only());
+        doc.checkpoint(&Position { x: 0, y: 4000 });
+        doc.insert(&Position { x: 0, y: 4000 }, 'x');
+        assert_eq!(doc.row(4000).map(Row::as_str), Some("line 4000"));
+        assert!(!doc.is_modified());
+        assert_eq!(doc.undo(&Position { x: 0, y: 4000 }), None);
+        assert_eq!(doc.save().unwrap_err().kind(), ErrorKind::PermissionDenied);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn piped_text_is_unnamed_and_read_only_only_when_viewed() {
+        //what from_stdin makes of piped bytes
+        let mut doc = Document::from_bytes(b"one\r\ntwo\r\n");
+        assert_eq!(lines(&doc), ["one", "two"]);
+        assert_eq!(doc.file_name, None);
+        assert_eq!(doc.line_ending(), LineEnding::CrLf);
+        assert!(!doc.is_read_only());
+
+        //--view
+        doc.set_read_only(true);
+        doc.checkpoint(&Position { x: 0, y: 0 });
+        doc.insert(&Position { x: 0, y: 0 }, 'x');
+        doc.delete(&Position { x: 0, y: 1 });
+        assert_eq!(lines(&doc), ["one", "two"]);
+        assert!(!doc.is_modified());
+
+        doc.set_read_only(false);
+        doc.insert(&Position { x: 3, y: 0 }, '!');
+        assert_eq!(lines(&doc), ["one!", "two"]);
+        assert!(doc.is_modified());
+
+        //there is nowhere to save it until it has a name
+        assert_eq!(doc.save().unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert!(doc.is_modified());
+        let (dir, file) = temp_file("piped", "saved.txt", b"");
+        doc.set_file_name(&file);
+        doc.save().unwrap();
+        assert!(!doc.is_modified());
+        assert_eq!(fs::read(&file).unwrap(), b"one!\r\ntwo\r\n");
+        fs::remove_dir_all(dir).unwrap();
+
+        //piped binary data is shown as hex and stays read-only
+        let mut doc = Document::from_bytes(b"\x7FELF\x02\x01\x01\0\0\0");
+        doc.set_read_only(false);
+        assert!(doc.is_binary() && doc.is_read_only());
+        assert!(lines(&doc)[0].starts_with("00000000  7f 45 4c 46"));
+    }
+
+    #[test]
+    fn filter_output_replaces_the_selected_lines_as_one_edit() {
+        let mut doc = document("header\nc\na\nb\nfooter\n");
+        let at = |y| Position { x: 0, y };
+        assert_eq!(doc.filter_rows("sort", 1..4, &at(2)), Ok(Some(1..4)));
+        assert_eq!(lines(&doc), ["header", "a", "b", "c", "footer"]);
+        //output that is what went in is no edit at all
+        assert_eq!(doc.filter_rows("cat", 0..5, &at(0)), Ok(None));
+        assert!(doc.filter_rows("exit 1", 1..3, &at(0)).is_err());
+        //output can be longer or shorter than what went in
+        let joined = doc.filter_rows("tr -d '\\n'; echo; echo end", 1..4, &at(3));
+        assert_eq!(joined, Ok(Some(1..3)));
+        assert_eq!(lines(&doc), ["header", "abc", "end", "footer"]);
+
+        assert_eq!(doc.undo(&at(2)), Some(at(3)));
+        assert_eq!(lines(&doc), ["header", "a", "b", "c", "footer"]);
+        assert_eq!(doc.undo(&at(3)), Some(at(2)));
+        assert_eq!(lines(&doc), ["header", "c", "a", "b", "footer"]);
+        assert_eq!(doc.undo(&at(2)), None);
+        doc.redo(&at(2));
+        assert_eq!(lines(&doc), ["header", "a", "b", "c", "footer"]);
+
+        doc.set_read_only(true);
+        assert_eq!(doc.filter_rows("sort -r", 1..4, &at(1)), Ok(Some(1..1)));
+        assert_eq!(lines(&doc), ["header", "a", "b", "c", "footer"]);
+    }
+
+    #[test]
+    fn block_edits_go_by_screen_column() {
+        //wide characters take two columns, an accented e is one grapheme of one column
+        let mut doc = document("ab日本c\ne\u{301}xyz\n日本語\n");
+        assert_eq!(doc.block_text(0..3, 2, 4), ["日", "yz", "本"]);
+        let cut = doc.block_text(0..3, 2, 4);
+        doc.checkpoint(&Position { x: 2, y: 0 });
+        doc.delete_block(0..3, 2, 4);
+        assert_eq!(lines(&doc), ["ab本c", "e\u{301}x", "日語"]);
+        doc.insert_block(0, 2, &cut);
+        assert_eq!(lines(&doc), ["ab日本c", "e\u{301}xyz", "日本語"]);
+
+        //short rows are padded out to the column, rows are added past the end
+        doc.checkpoint(&Position { x: 0, y: 1 });
+        let bars = vec!["|".to_string(); 3];
+        doc.insert_block(1, 7, &bars);
+        assert_eq!(lines(&doc), ["ab日本c", "e\u{301}xyz   |", "日本語 |", "       |"]);
+        assert_eq!(doc.undo(&Position { x: 0, y: 3 }), Some(Position { x: 0, y: 1 }));
+        assert_eq!(lines(&doc), ["ab日本c", "e\u{301}xyz", "日本語"]);
+    }
+
+    #[test]
+    fn editorconfig_settings_are_applied_on_save() {
+        let config = "root = true\n[*]\ntrim_trailing_whitespace = true\n\
+                      insert_final_newline = false\nend_of_line = crlf\n\
+                      [*.md]\ntrim_trailing_whitespace = false\ninsert_final_newline = true\n";
+        let (dir, _) = temp_file("editorconfig", ".editorconfig", config.as_bytes());
+        let text = dir.join("notes.txt").to_string_lossy().to_string();
+        fs::write(&text, "a  \nb\t\n").unwrap();
+        let mut doc = Document::open(&text).unwrap();
+        doc.checkpoint(&Position { x: 0, y: 0 });
+        doc.insert(&Position { x: 0, y: 0 }, '>');
+        doc.save().unwrap();
+        assert_eq!(fs::read(&text).unwrap(), b">a\r\nb");
+        assert_eq!(lines(&doc), [">a", "b"]);
+        assert_eq!(doc.line_ending(), LineEnding::CrLf);
+        assert!(!doc.is_modified());
+        //the trimming is undone together with the edit it was saved after
+        doc.undo(&Position { x: 0, y: 0 });
+        assert_eq!(lines(&doc), ["a  ", "b\t"]);
+        assert!(doc.is_modified());
+
+        let markdown = dir.join("notes.md").to_string_lossy().to_string();
+        fs::write(&markdown, "a  \nb").unwrap();
+        let mut doc = Document::open(&markdown).unwrap();
+        doc.save().unwrap();
+        assert_eq!(fs::read(&markdown).unwrap(), b"a  \r\nb\r\n");
+        fs::remove_dir_all(dir).unwrap();
     }
 }
@@ -1,14 +1,36 @@
+use crate::completion::{self, Completion};
+use crate::config::Config;
+use crate::document::Case;
+use crate::editorconfig::IndentStyle;
+use crate::finder::Finder;
+use crate::git::LineChange;
+use crate::macros::{Macros, Replay};
+use crate::session::{FileState, Session};
+use crate::spell::SpellChecker;
+use crate::status_line::StatusLine;
 use crate::Document;
 use crate::Row;
 use crate::Terminal;
 use std::env;
+use std::ops::Range;
+use std::path::Path;
 use std::time::Duration;
 use std::time::Instant;
 use termion::color;
 use termion::event::Key;
+use unicode_segmentation::UnicodeSegmentation;
 
 const STATUS_BG_COLOR: color::Rgb = color::Rgb(239, 239, 239);
 const STATUS_FG_COLOR: color::Rgb = color::Rgb(63, 63, 63);
+const GIT_ADDED_COLOR: color::Rgb = color::Rgb(80, 200, 120);
+const GIT_MODIFIED_COLOR: color::Rgb = color::Rgb(90, 150, 230);
+const GIT_DELETED_COLOR: color::Rgb = color::Rgb(230, 80, 80);
+const SELECTION_BG_COLOR: color::Rgb = color::Rgb(70, 90, 140);
+const BOOKMARK_COLOR: color::Rgb = color::Rgb(220, 180, 60);
+const TRAILING_WHITESPACE_COLOR: color::Rgb = color::Rgb(150, 50, 50);
+const MIXED_INDENT_COLOR: color::Rgb = color::Rgb(130, 100, 40);
+//marker plus a space between it and the text
+const GUTTER_WIDTH: usize = 2;
 
 struct StatusMessage {
     text: String,
@@ -23,6 +45,26 @@ impl StatusMessage {
         }
     }
 }
+//runs of typing or deleting are undone in one go
+#[derive(Clone, Copy, PartialEq)]
+enum UndoGroup {
+    Typing,
+    Deleting,
+}
+
+//text copied or cut, a block is pasted as a rectangle at the cursor's column
+struct Clipboard {
+    lines: Vec<String>,
+    block: bool,
+}
+
+//a document that is open but not currently shown, along with where we were in it
+struct Buffer {
+    document: Document,
+    cursor_position: Position,
+    offset: Position,
+}
+
 pub struct Editor {
     should_quit: bool,
     terminal: Terminal,
@@ -30,8 +72,29 @@ pub struct Editor {
     document: Document,
     offset: Position,
     status_message: StatusMessage,
+    buffers: Vec<Buffer>,
+    finder: Option<Finder>,
+    macros: Macros,
+    replay: Replay,
+    //pager mode: editing is disabled and single keys navigate
+    view_mode: bool,
+    last_search: Option<String>,
+    //the other end of the selection, the cursor being this end
+    selection_anchor: Option<Position>,
+    //the selection is the rectangle between the anchor and the cursor, by screen columns
+    block_selection: bool,
+    clipboard: Option<Clipboard>,
+    undo_group: Option<UndoGroup>,
+    config: Config,
+    status_line: StatusLine,
+    completion: Option<Completion>,
+    //None without a word list
+    spell: Option<SpellChecker>,
+    session: Session,
+    //off with --no-session: nothing is restored and nothing is written on quit
+    remember_session: bool,
 }
-#[derive(Default)]
+#[derive(Default, Clone, Copy, PartialEq, Debug)]
 pub struct Position {
     pub x: usize,
     pub y: usize,
@@ -41,9 +104,18 @@ const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 impl Editor {
     pub fn run(&mut self) {
+        self.scroll();
         loop {
-            if let Err(error) = self.refresh_screen() {
-                die(error);
+            self.document.update_git_changes();
+            if self.replay.take_stopped() {
+                self.status_message =
+                    StatusMessage::from("Macro too long, replay stopped".to_string());
+            }
+            //no point drawing every step of a macro replay
+            if self.replay.is_empty() || self.should_quit {
+                if let Err(error) = self.refresh_screen() {
+                    die(error);
+                }
             }
             if self.should_quit {
                 break;
@@ -54,15 +126,40 @@ impl Editor {
         }
     }
 
+    //usage: txt-editor [--view] [--no-session] [FILE | DIRECTORY | -], where `-` reads the document from stdin.
+    //Without a file the buffers open when the editor last quit are opened again.
     pub fn default() -> Self {
-        let args: Vec<String> = env::args().collect();
-        let mut init_status = String::from("HELP: Ctrl-Q = quit | Ctrl-S = save");
-
-        let document = if args.len() > 1 {
-            let filename = &args[1];
-            let doc = Document::open(filename);
-            if doc.is_ok() {
-                doc.unwrap()
+        let args: Vec<String> = env::args().skip(1).collect();
+        let view_mode = args.iter().any(|arg| arg == "--view");
+        let remember_session = !args.iter().any(|arg| arg == "--no-session");
+        let path = args
+            .iter()
+            .find(|arg| *arg == "-" || !arg.starts_with("--"));
+        let mut init_status = if view_mode {
+            String::from(
+                "HELP: q = quit | / = search | n/N = next/previous match | Space/b = page down/up",
+            )
+        } else {
+            String::from(
+                "HELP: Ctrl-Q = quit | Ctrl-S = save | Ctrl-F = find | Ctrl-P = open file | Alt-,/Alt-. = switch buffer",
+            )
+        };
+
+        let from_stdin = path.is_some_and(|path| path == "-")
+            || (path.is_none() && !termion::is_tty(&std::io::stdin()));
+        let mut document = if from_stdin {
+            Document::from_stdin().unwrap_or_else(|_| {
+                init_status = String::from("Error: Could not read from stdin");
+                Document::default()
+            })
+        } else if let Some(filename) = path {
+            let doc = if Path::new(filename).is_dir() {
+                Document::open_directory(filename)
+            } else {
+                Document::open(filename)
+            };
+            if let Ok(doc) = doc {
+                doc
             } else {
                 init_status = format!("Error: Could not open file {}", &filename);
                 Document::default()
@@ -70,15 +167,130 @@ impl Editor {
         } else {
             Document::default()
         };
+        if view_mode {
+            document.set_read_only(true);
+        }
 
-        Self {
+        let config = Config::load();
+        let session = if remember_session {
+            Session::load()
+        } else {
+            Session::default()
+        };
+        let mut editor = Self {
             should_quit: false,
             terminal: Terminal::default().expect("Failed to initialize terminal"),
             cursor_position: Position::default(),
-            document: document,
+            document,
             offset: Position::default(),
             status_message: StatusMessage::from(init_status),
+            buffers: Vec::new(),
+            finder: None,
+            macros: Macros::load(),
+            replay: Replay::default(),
+            view_mode,
+            last_search: session.search_history().last().cloned(),
+            selection_anchor: None,
+            block_selection: false,
+            clipboard: None,
+            undo_group: None,
+            status_line: StatusLine::from_config(&config),
+            spell: SpellChecker::load(&config),
+            config,
+            completion: None,
+            session,
+            remember_session,
+        };
+        if path.is_none() && !from_stdin {
+            editor.reopen_files();
+        } else if let Some(state) = editor.file_state(&editor.document) {
+            editor.cursor_position = state.cursor_position;
+            editor.offset = state.offset;
+            editor.clamp_cursor();
         }
+        editor
+    }
+
+    //brings back the buffers that were open when the editor last quit
+    fn reopen_files(&mut self) {
+        let paths = self.session.open_files().to_vec();
+        let mut buffers = Vec::new();
+        for path in paths {
+            let name = path.to_string_lossy().to_string();
+            let document = if path.is_dir() {
+                Document::open_directory(&name)
+            } else {
+                Document::open(&name)
+            };
+            if let Ok(document) = document {
+                buffers.push(self.restored_buffer(document));
+            }
+        }
+        if !buffers.is_empty() {
+            self.replace_buffer(buffers.remove(0));
+            self.buffers = buffers;
+        }
+    }
+
+    fn file_state(&self, document: &Document) -> Option<FileState> {
+        if !self.remember_session {
+            return None;
+        }
+        document
+            .file_name
+            .as_ref()
+            .and_then(|name| self.session.file_state(Path::new(name)))
+    }
+
+    //a buffer for a freshly opened document, at the position it was left at last time
+    fn restored_buffer(&self, document: Document) -> Buffer {
+        let state = self.file_state(&document);
+        let mut buffer = Buffer {
+            document,
+            cursor_position: state.map_or_else(Position::default, |state| state.cursor_position),
+            offset: state.map_or_else(Position::default, |state| state.offset),
+        };
+        //the file may have shrunk since, large files only know their length once indexed
+        if !buffer.document.is_large() {
+            let y = buffer.cursor_position.y.min(buffer.document.len());
+            let x = buffer.document.row(y).map_or(0, Row::len);
+            buffer.cursor_position = Position {
+                x: buffer.cursor_position.x.min(x),
+                y,
+            };
+            buffer.offset.y = buffer.offset.y.min(y);
+        }
+        buffer
+    }
+
+    //records where we are in every open file and which ones are open, then stops the editor
+    fn quit(&mut self) {
+        self.should_quit = true;
+        if !self.remember_session {
+            return;
+        }
+        let current = (&self.document, self.cursor_position, self.offset);
+        let others = self
+            .buffers
+            .iter()
+            .map(|buffer| (&buffer.document, buffer.cursor_position, buffer.offset));
+        //the current file goes last so it ends up as the most recent one
+        for (document, cursor_position, offset) in others.clone().chain(std::iter::once(current)) {
+            if let Some(name) = &document.file_name {
+                let state = FileState {
+                    cursor_position,
+                    offset,
+                };
+                self.session.set_file_state(Path::new(name), state);
+            }
+        }
+        let open_files: Vec<&Path> = std::iter::once(current)
+            .chain(others)
+            .filter_map(|(document, _, _)| document.file_name.as_deref().map(Path::new))
+            .collect();
+        self.session.set_open_files(&open_files);
+        //not being able to write the session is no reason to keep the user from quitting
+        let _ = self.session.save();
     }
 
     fn refresh_screen(&self) -> Result<(), std::io::Error> {
@@ -93,7 +305,11 @@ impl Editor {
             self.draw_message_bar();
             //now the cursor position refers to the position the cursor is in the file, we need to normalize it by sub the offset
             Terminal::cursor_position(&Position {
-                x: self.cursor_position.x.saturating_sub(self.offset.x),
+                x: self
+                    .cursor_position
+                    .x
+                    .saturating_sub(self.offset.x)
+                    .saturating_add(self.gutter_width()),
                 y: self.cursor_position.y.saturating_sub(self.offset.y),
             });
         }
@@ -101,50 +317,302 @@ impl Editor {
         Terminal::flush()
     }
 
-    pub fn draw_row(&self, row: &Row) {
-        let width = self.terminal.size().width as usize;
+    //the row with the selection, trailing whitespace and bad indentation highlighted,
+    //the selection drawn over the others
+    pub fn draw_row(&self, row: &Row, index: usize) {
+        let width = self.text_width();
         let start = self.offset.x;
         let end = width + self.offset.x;
-        let row = row.render(start, end);
-        println!("{}\r", row);
+        let misspelled = self.misspellings(row);
+        let mut highlights = Vec::new();
+        if let Some((from, to)) = self.selected_columns(index, row) {
+            highlights.push((from, to, SELECTION_BG_COLOR));
+        }
+        if !self.document.is_read_only() {
+            //not while typing at the end of the line
+            if let Some(from) = row.trailing_whitespace_start() {
+                if index != self.cursor_position.y {
+                    highlights.push((from, row.len(), TRAILING_WHITESPACE_COLOR));
+                }
+            }
+            if self.is_mixed_indentation(row) {
+                let indentation = row.indentation().graphemes(true).count();
+                highlights.push((0, indentation, MIXED_INDENT_COLOR));
+            }
+        }
+
+        let mut boundaries = vec![start, end];
+        for (from, to, _) in &highlights {
+            boundaries.push((*from).clamp(start, end));
+            boundaries.push((*to).clamp(start, end));
+        }
+        boundaries.sort_unstable();
+        boundaries.dedup();
+        for pair in boundaries.windows(2) {
+            let (from, to) = (pair[0], pair[1]);
+            let color = highlights
+                .iter()
+                .find(|(highlight_from, highlight_to, _)| {
+                    *highlight_from <= from && to <= *highlight_to
+                })
+                .map(|(_, _, color)| *color);
+            match color {
+                Some(color) => {
+                    Terminal::set_bg_color(color);
+                    print!("{}", row.render_underlined(from, to, &misspelled));
+                    Terminal::reset_bg_color();
+                }
+                None => print!("{}", row.render_underlined(from, to, &misspelled)),
+            }
+        }
+        println!("\r");
+    }
+
+    //the words of a row not in the dictionary, nothing for buffers that are not really text
+    fn misspellings(&self, row: &Row) -> Vec<Range<usize>> {
+        let document = &self.document;
+        match &self.spell {
+            Some(spell)
+                if !document.is_binary() && !document.is_directory() && !document.is_large() =>
+            {
+                spell.misspellings(row.as_str(), &document.file_type())
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    //indentation mixing tabs and spaces, or using the one .editorconfig does not ask for
+    fn is_mixed_indentation(&self, row: &Row) -> bool {
+        let indentation = row.indentation();
+        let settings = self.document.settings();
+        match settings.indent_style {
+            Some(IndentStyle::Space) => indentation.contains('\t'),
+            Some(IndentStyle::Tab) => indentation.starts_with(&" ".repeat(settings.indent_width())),
+            None => indentation.contains('\t') && indentation.contains(' '),
+        }
+    }
+
+    //start and end of the selection, in order
+    fn selection(&self) -> Option<(Position, Position)> {
+        let anchor = self.selection_anchor?;
+        let cursor = self.cursor_position;
+        if anchor == cursor {
+            None
+        } else if (anchor.y, anchor.x) < (cursor.y, cursor.x) {
+            Some((anchor, cursor))
+        } else {
+            Some((cursor, anchor))
+        }
+    }
+
+    //the lines of a block selection and the screen columns it spans on them, left to right
+    fn block(&self) -> Option<(std::ops::Range<usize>, usize, usize)> {
+        if !self.block_selection {
+            return None;
+        }
+        let anchor = self.selection_anchor?;
+        let cursor = self.cursor_position;
+        let column = |position: Position| {
+            self.document
+                .row(position.y)
+                .map_or(0, |row| row.display_column(position.x))
+        };
+        let (from, to) = (column(anchor), column(cursor));
+        Some((
+            anchor.y.min(cursor.y)..anchor.y.max(cursor.y) + 1,
+            from.min(to),
+            from.max(to),
+        ))
+    }
+
+    //the part of row `index` that is selected
+    fn selected_columns(&self, index: usize, row: &Row) -> Option<(usize, usize)> {
+        if let Some((lines, left, right)) = self.block() {
+            if !lines.contains(&index) {
+                return None;
+            }
+            return Some((row.index_at_column(left), row.index_at_column(right)));
+        }
+        let (start, end) = self.selection()?;
+        if index < start.y || index > end.y {
+            return None;
+        }
+        let from = if index == start.y { start.x } else { 0 };
+        let to = if index == end.y { end.x } else { row.len() };
+        Some((from, to))
+    }
+
+    //the lines touched by the selection, a selection ending at the start of a line leaves that line out
+    fn selected_lines(&self) -> Option<std::ops::Range<usize>> {
+        let (start, end) = self.selection()?;
+        if end.x == 0 && end.y > start.y {
+            Some(start.y..end.y)
+        } else {
+            Some(start.y..end.y + 1)
+        }
     }
 
     fn draw_rows(&self) {
         let height = self.terminal.size().height;
+        let list_start = height.saturating_sub(self.finder_height() as u16);
         for terminal_row in 0..height {
             Terminal::clear_row();
-            if let Some(row) = self.document.row(terminal_row as usize + self.offset.y) {
-                self.draw_row(row);
+            if terminal_row >= list_start {
+                self.draw_finder_row((terminal_row - list_start) as usize);
+                continue;
+            }
+            let index = terminal_row as usize + self.offset.y;
+            if let Some(row) = self.document.row(index) {
+                self.draw_gutter(index);
+                self.draw_row(row, index);
             } else if self.document.is_empty() && terminal_row == height / 3 {
                 self.draw_welcome_message();
             } else {
                 println!("~\r");
             }
         }
+        self.draw_completion();
     }
 
-    fn draw_status_bar(&self) {
-        let mut status;
+    //the completion popup, drawn over the text just below (or above) the word being completed
+    fn draw_completion(&self) {
+        let completion = match &self.completion {
+            Some(completion) => completion,
+            None => return,
+        };
+        let height = self.terminal.size().height as usize;
         let width = self.terminal.size().width as usize;
-        let mut filename = "[No Name]".to_string();
-        if let Some(name) = &self.document.file_name {
-            filename = name.clone();
-            filename.truncate(20);
+        let candidates = completion.candidates();
+        let shown = candidates.len().min(completion::VISIBLE_CANDIDATES);
+        let first = completion
+            .selected()
+            .saturating_sub(shown.saturating_sub(1));
+        let popup_width = candidates
+            .iter()
+            .map(|word| word.graphemes(true).count() + 2)
+            .max()
+            .unwrap_or(0)
+            .min(width);
+
+        let cursor_row = self.cursor_position.y.saturating_sub(self.offset.y);
+        let top = if cursor_row + 1 + shown <= height {
+            cursor_row + 1
+        } else {
+            cursor_row.saturating_sub(shown)
+        };
+        let word_start = self
+            .cursor_position
+            .x
+            .saturating_sub(completion.prefix().graphemes(true).count());
+        let left = (word_start.saturating_sub(self.offset.x) + self.gutter_width())
+            .min(width.saturating_sub(popup_width));
+
+        for (line, index) in (first..first + shown).enumerate() {
+            Terminal::cursor_position(&Position {
+                x: left,
+                y: top + line,
+            });
+            let mut item = format!(" {}", candidates[index]);
+            item = item.graphemes(true).take(popup_width).collect();
+            let padding = popup_width.saturating_sub(item.graphemes(true).count());
+            item.push_str(&" ".repeat(padding));
+            if index == completion.selected() {
+                Terminal::set_bg_color(STATUS_BG_COLOR);
+                Terminal::set_fg_color(STATUS_FG_COLOR);
+            } else {
+                Terminal::set_bg_color(SELECTION_BG_COLOR);
+            }
+            print!("{}", item);
+            Terminal::reset_fg_color();
+            Terminal::reset_bg_color();
+        }
+    }
+
+    fn gutter_width(&self) -> usize {
+        if self.document.has_git() || !self.document.bookmarks().is_empty() {
+            GUTTER_WIDTH
+        } else {
+            0
+        }
+    }
+
+    fn text_width(&self) -> usize {
+        (self.terminal.size().width as usize).saturating_sub(self.gutter_width())
+    }
+
+    //git change markers, or the name of a bookmark on an unchanged line
+    fn draw_gutter(&self, index: usize) {
+        if self.gutter_width() == 0 {
+            return;
         }
+        let (marker, color) = match self.document.git_change(index) {
+            Some(LineChange::Added) => ("+".to_string(), GIT_ADDED_COLOR),
+            Some(LineChange::Modified) => ("~".to_string(), GIT_MODIFIED_COLOR),
+            Some(LineChange::Deleted) => ("_".to_string(), GIT_DELETED_COLOR),
+            None => match self.document.bookmarks().on_line(index) {
+                Some(name) => (name.to_string(), BOOKMARK_COLOR),
+                None => {
+                    print!("{}", " ".repeat(GUTTER_WIDTH));
+                    return;
+                }
+            },
+        };
+        Terminal::set_fg_color(color);
+        print!("{:<width$}", marker, width = GUTTER_WIDTH);
+        Terminal::reset_fg_color();
+    }
 
-        status = format!("{} - {} lines", filename, self.document.len());
-        let line_indicator = format!(
-            "{}/{}",
-            self.cursor_position.y.saturating_add(1),
-            self.document.len()
-        );
-        let len = status.len() + line_indicator.len();
+    //number of rows the file finder overlay takes at the bottom of the text area
+    fn finder_height(&self) -> usize {
+        match &self.finder {
+            Some(finder) => finder
+                .match_count()
+                .clamp(1, (self.terminal.size().height as usize / 2).max(1)),
+            None => 0,
+        }
+    }
 
-        if width > len {
-            status.push_str(&" ".repeat(width - len))
+    fn draw_finder_row(&self, index: usize) {
+        let finder = match &self.finder {
+            Some(finder) => finder,
+            None => return,
+        };
+        let width = self.terminal.size().width as usize;
+        if finder.match_count() == 0 {
+            println!("  no matches in {} files\r", finder.file_count());
+            return;
         }
-        status = format!("{}{}", status, line_indicator);
-        status.truncate(width);
+        //keep the selected entry in view
+        let first = finder
+            .selected()
+            .saturating_sub(self.finder_height().saturating_sub(1));
+        let index = first + index;
+        if let Some(path) = finder.matches().nth(index) {
+            let mut line = format!("  {}", path);
+            if index == finder.selected() {
+                line = format!("> {}", path);
+                line.truncate(width);
+                line.push_str(&" ".repeat(width.saturating_sub(line.len())));
+                Terminal::set_bg_color(STATUS_BG_COLOR);
+                Terminal::set_fg_color(STATUS_FG_COLOR);
+                print!("{}", line);
+                Terminal::reset_fg_color();
+                Terminal::reset_bg_color();
+                println!("\r");
+                return;
+            }
+            line.truncate(width);
+            println!("{}\r", line);
+        } else {
+            println!("\r");
+        }
+    }
+
+    fn draw_status_bar(&self) {
+        let width = self.terminal.size().width as usize;
+        let status = self
+            .status_line
+            .render(width, |name| self.status_value(name));
         Terminal::set_bg_color(STATUS_BG_COLOR);
         Terminal::set_fg_color(STATUS_FG_COLOR);
         println!("{}\r", status);
@@ -152,6 +620,79 @@ impl Editor {
         Terminal::reset_bg_color()
     }
 
+    //what the {placeholders} of the status line stand for, None for unknown ones
+    fn status_value(&self, name: &str) -> Option<String> {
+        let document = &self.document;
+        let Position { x, y } = self.cursor_position;
+        let value = match name {
+            "file" => document
+                .file_name
+                .clone()
+                .unwrap_or_else(|| "[No Name]".to_string()),
+            "modified" if document.is_modified() => " [+]".to_string(),
+            "modified" => String::new(),
+            //the line count keeps growing while a large file is indexed
+            "lines" if document.is_indexing() => format!("{}+", document.len()),
+            "lines" => document.len().to_string(),
+            "line" => y.saturating_add(1).to_string(),
+            "column" => {
+                let column = document.row(y).map_or(0, |row| row.display_column(x));
+                column.saturating_add(1).to_string()
+            }
+            "percent" => format!(
+                "{}%",
+                (y.saturating_add(1) * 100 / document.len().max(1)).min(100)
+            ),
+            "type" => document.file_type().name().to_string(),
+            "encoding" if document.is_binary() => "binary".to_string(),
+            "encoding" if document.is_large() => "large file".to_string(),
+            "encoding" => document.encoding().name().to_string(),
+            "eol" if document.is_binary() || document.is_large() => String::new(),
+            "eol" => document.line_ending().name().to_string(),
+            "mode" => self.mode().to_string(),
+            "selection" => self.selection_size(),
+            "recording" => self
+                .macros
+                .recording()
+                .map_or_else(String::new, |register| format!("recording {}", register)),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    fn mode(&self) -> &'static str {
+        if self.view_mode {
+            "VIEW"
+        } else if self.document.is_read_only() {
+            "READ-ONLY"
+        } else if self.block().is_some() {
+            "BLOCK"
+        } else if self.selection().is_some() {
+            "SELECT"
+        } else {
+            ""
+        }
+    }
+
+    fn selection_size(&self) -> String {
+        if let Some((lines, left, right)) = self.block() {
+            return format!("{}x{} block", lines.len(), right - left);
+        }
+        let (start, end) = match self.selection() {
+            Some(selection) => selection,
+            None => return String::new(),
+        };
+        let chars = self
+            .document
+            .text_between(&start, &end)
+            .graphemes(true)
+            .count();
+        match self.selected_lines() {
+            Some(lines) if lines.len() > 1 => format!("{} lines, {} chars", lines.len(), chars),
+            _ => format!("{} chars", chars),
+        }
+    }
+
     fn draw_message_bar(&self) {
         Terminal::clear_row();
         let message = &self.status_message;
@@ -174,28 +715,170 @@ impl Editor {
     }
 
     fn process_keypress(&mut self) -> Result<(), std::io::Error> {
-        let pressed_key = Terminal::read_key()?;
+        let pressed_key = self.read_key()?;
+        if self.completion.is_some() && self.process_completion_key(pressed_key) {
+            self.scroll();
+            return Ok(());
+        }
+        if self.view_mode && self.process_view_key(pressed_key)? {
+            self.scroll();
+            return Ok(());
+        }
         match pressed_key {
-            Key::Ctrl('q') => self.should_quit = true,
-            Key::Ctrl('s') => {
-                if self.document.save().is_err() {
-                } else if self.document.save().is_ok() {
+            Key::Ctrl('q') => self.quit(),
+            Key::Ctrl('r') => self.toggle_recording()?,
+            Key::Ctrl('e') => self.replay_macro()?,
+            Key::Ctrl('p') => self.find_file()?,
+            Key::Ctrl('f') => self.search()?,
+            Key::Alt('.') => self.next_buffer(),
+            Key::Alt(',') => self.previous_buffer(),
+            Key::Ctrl('c') => {
+                self.copy();
+            }
+            Key::Alt('v') => self.toggle_block_selection(),
+            Key::Ctrl('b') => self.set_bookmark()?,
+            Key::Alt('b') => self.go_to_bookmark()?,
+            Key::Ctrl('g') => self.go_to_line()?,
+            Key::F(7) => self.next_misspelling(),
+            Key::F(8) => self.correct_word()?,
+            Key::AltLeft => match self.document.bookmarks_mut().back(self.cursor_position) {
+                Some(position) => self.move_to(position),
+                None => self.status_message = StatusMessage::from("No earlier jumps".to_string()),
+            },
+            Key::AltRight => match self.document.bookmarks_mut().forward() {
+                Some(position) => self.move_to(position),
+                None => self.status_message = StatusMessage::from("No later jumps".to_string()),
+            },
+            Key::Char('\n') if self.document.is_directory() => self.open_directory_entry(),
+            Key::Char(_)
+            | Key::Delete
+            | Key::Backspace
+            | Key::Ctrl('z')
+            | Key::Ctrl('y')
+            | Key::Alt('|')
+            | Key::Ctrl('x')
+            | Key::Ctrl('v')
+            | Key::Ctrl('d')
+            | Key::Ctrl('k')
+            | Key::Ctrl('n')
+            | Key::AltUp
+            | Key::AltDown
+            | Key::Alt('j' | 's' | 'u' | 'l' | 't' | 'r')
+                if self.document.is_read_only() =>
+            {
+                self.status_message = StatusMessage::from("Buffer is read-only".to_string());
+            }
+            Key::Ctrl('s') => self.save()?,
+            Key::Ctrl('z') => match self.document.undo(&self.cursor_position) {
+                Some(position) => self.restore_cursor(position),
+                None => self.status_message = StatusMessage::from("Nothing to undo".to_string()),
+            },
+            Key::Ctrl('y') => match self.document.redo(&self.cursor_position) {
+                Some(position) => self.restore_cursor(position),
+                None => self.status_message = StatusMessage::from("Nothing to redo".to_string()),
+            },
+            Key::Alt('|') => self.filter_selection()?,
+            Key::Ctrl('x') => self.cut(),
+            Key::Ctrl('v') => self.paste(),
+            Key::Ctrl('d') => {
+                self.begin_edit(None);
+                let lines = self.current_lines();
+                if let Some(copy) = self.document.duplicate_lines(lines.clone()) {
+                    self.follow_lines(lines.start, copy.start);
+                }
+            }
+            Key::AltUp | Key::AltDown => {
+                self.begin_edit(None);
+                let lines = self.current_lines();
+                let up = pressed_key == Key::AltUp;
+                if let Some(moved) = self.document.move_lines(lines.clone(), up) {
+                    self.follow_lines(lines.start, moved.start);
+                }
+            }
+            Key::Alt('j') => {
+                self.begin_edit(None);
+                let lines = self.current_lines();
+                if let Some(x) = self.document.join_lines(lines.clone()) {
+                    self.selection_anchor = None;
+                    self.cursor_position = Position { x, y: lines.start };
+                }
+            }
+            Key::Alt('s') => {
+                self.begin_edit(None);
+                self.document.sort_lines(self.current_lines());
+            }
+            Key::Ctrl('k') => {
+                self.begin_edit(None);
+                self.selection_anchor = None;
+                self.document.delete_to_end_of_line(&self.cursor_position);
+            }
+            Key::Alt('u') => self.convert_case(Case::Upper),
+            Key::Alt('l') => self.convert_case(Case::Lower),
+            Key::Alt('t') => self.convert_case(Case::Title),
+            Key::Ctrl('n') => self.start_completion(),
+            Key::Esc => self.selection_anchor = None,
+            Key::ShiftUp | Key::ShiftDown | Key::ShiftLeft | Key::ShiftRight => {
+                if self.selection_anchor.is_none() {
+                    self.selection_anchor = Some(self.cursor_position);
+                    self.block_selection = false;
+                }
+                self.move_cursor(match pressed_key {
+                    Key::ShiftUp => Key::Up,
+                    Key::ShiftDown => Key::Down,
+                    Key::ShiftLeft => Key::Left,
+                    _ => Key::Right,
+                });
+            }
+            Key::Alt('n') => match self.document.next_hunk(self.cursor_position.y) {
+                Some(y) => self.jump_to(Position { x: 0, y }),
+                None => self.status_message = StatusMessage::from("No more changes".to_string()),
+            },
+            Key::Alt('p') => match self.document.previous_hunk(self.cursor_position.y) {
+                Some(y) => self.jump_to(Position { x: 0, y }),
+                None => {
+                    self.status_message = StatusMessage::from("No previous changes".to_string())
+                }
+            },
+            Key::Alt('r') => match self.revert_hunk() {
+                Some(y) => self.cursor_position = Position { x: 0, y },
+                None => {
                     self.status_message =
-                        StatusMessage::from("File saved successfully".to_string());
-                } else {
-                    self.status_message = StatusMessage::from("Unable to save file".to_string());
+                        StatusMessage::from("No change under the cursor".to_string())
+                }
+            },
+            Key::Char(c) if c != '\n' && self.block().is_some() => self.type_in_block(c),
+            Key::Delete | Key::Backspace if self.block().is_some() => {
+                self.delete_in_block(pressed_key)
+            }
+            Key::Char('\t')
+                if self.document.settings().indent_style == Some(IndentStyle::Space) =>
+            {
+                self.begin_edit(Some(UndoGroup::Typing));
+                self.selection_anchor = None;
+                let Position { x, y } = self.cursor_position;
+                let column = self.document.row(y).map_or(0, |row| row.display_column(x));
+                let indent_width = self.document.settings().indent_width();
+                for _ in 0..indent_width - column % indent_width {
+                    self.document.insert(&self.cursor_position, ' ');
+                    self.move_cursor(Key::Right);
                 }
             }
             Key::Char(c) => {
+                self.begin_edit(Some(UndoGroup::Typing));
+                self.selection_anchor = None;
                 self.document.insert(&self.cursor_position, c);
                 self.move_cursor(Key::Right);
             }
-            Key::Delete => self.document.delete(&self.cursor_position),
-            Key::Backspace => {
-                if self.cursor_position.x > 0 || self.cursor_position.y > 0 {
-                    self.move_cursor(Key::Left);
-                    self.document.delete(&self.cursor_position);
-                }
+            Key::Delete => {
+                self.begin_edit(Some(UndoGroup::Deleting));
+                self.selection_anchor = None;
+                self.document.delete(&self.cursor_position);
+            }
+            Key::Backspace if self.cursor_position.x > 0 || self.cursor_position.y > 0 => {
+                self.begin_edit(Some(UndoGroup::Deleting));
+                self.selection_anchor = None;
+                self.move_cursor(Key::Left);
+                self.document.delete(&self.cursor_position);
             }
             Key::Up
             | Key::Down
@@ -204,13 +887,869 @@ impl Editor {
             | Key::PageDown
             | Key::PageUp
             | Key::End
-            | Key::Home => self.move_cursor(pressed_key),
+            | Key::Home => {
+                self.selection_anchor = None;
+                self.undo_group = None;
+                if matches!(pressed_key, Key::PageUp | Key::PageDown) {
+                    self.document
+                        .bookmarks_mut()
+                        .record_jump(self.cursor_position);
+                }
+                self.move_cursor(pressed_key);
+            }
             _ => (),
         }
         self.scroll();
         Ok(())
     }
 
+    //the selected lines, or the cursor's line without a selection
+    fn current_lines(&self) -> std::ops::Range<usize> {
+        let y = self.cursor_position.y;
+        self.selected_lines().unwrap_or(y..y + 1)
+    }
+
+    //keeps the cursor and the selection on lines that moved from `from` to `to`
+    fn follow_lines(&mut self, from: usize, to: usize) {
+        self.cursor_position.y = self.cursor_position.y - from + to;
+        if let Some(anchor) = &mut self.selection_anchor {
+            anchor.y = anchor.y - from + to;
+        }
+    }
+
+    //converts the selection, or the word at the cursor, keeping the converted text selected
+    fn convert_case(&mut self, case: Case) {
+        let (start, end) = match self
+            .selection()
+            .or_else(|| self.document.word_at(&self.cursor_position))
+        {
+            Some(range) => range,
+            None => return,
+        };
+        self.begin_edit(None);
+        let end = self.document.convert_case(&start, &end, case);
+        if self.selection_anchor.is_some() {
+            self.selection_anchor = Some(start);
+            self.cursor_position = end;
+        }
+        self.clamp_cursor();
+    }
+
+    //remembers where we were in the jump list before moving far
+    fn jump_to(&mut self, position: Position) {
+        self.document
+            .bookmarks_mut()
+            .record_jump(self.cursor_position);
+        self.move_to(position);
+    }
+
+    fn move_to(&mut self, position: Position) {
+        self.selection_anchor = None;
+        self.undo_group = None;
+        self.cursor_position = position;
+        if !self.document.is_large() {
+            self.clamp_cursor();
+        }
+    }
+
+    fn set_bookmark(&mut self) -> Result<(), std::io::Error> {
+        let name = self.prompt("Set bookmark (a-z, 0-9): ", |_, _, _| {})?;
+        match name.and_then(|name| name.chars().next()) {
+            Some(name) if name.is_ascii_lowercase() || name.is_ascii_digit() => {
+                self.document
+                    .bookmarks_mut()
+                    .set(name, self.cursor_position);
+                self.status_message = StatusMessage::from(format!("Bookmark {} set", name));
+            }
+            Some(_) => self.status_message = StatusMessage::from("Invalid bookmark".to_string()),
+            None => (),
+        }
+        Ok(())
+    }
+
+    fn go_to_bookmark(&mut self) -> Result<(), std::io::Error> {
+        let names: String = self.document.bookmarks().names().collect();
+        if names.is_empty() {
+            self.status_message = StatusMessage::from("No bookmarks".to_string());
+            return Ok(());
+        }
+        let prompt = format!("Go to bookmark ({}): ", names);
+        let name = match self.prompt(&prompt, |_, _, _| {})? {
+            Some(name) => name,
+            None => return Ok(()),
+        };
+        match name
+            .chars()
+            .next()
+            .and_then(|name| self.document.bookmarks().get(name))
+        {
+            Some(position) => self.jump_to(position),
+            None => self.status_message = StatusMessage::from(format!("No bookmark {}", name)),
+        }
+        Ok(())
+    }
+
+    //`line` or `line:column`, both counting from 1
+    fn go_to_line(&mut self) -> Result<(), std::io::Error> {
+        let input = match self.prompt("Go to line[:column]: ", |_, _, _| {})? {
+            Some(input) => input,
+            None => return Ok(()),
+        };
+        let (line, column) = match input.split_once(':') {
+            Some((line, column)) => (line, Some(column)),
+            None => (input.as_str(), None),
+        };
+        let line = line.trim().parse::<usize>();
+        let column = column.map_or(Ok(1), |column| column.trim().parse::<usize>());
+        match (line, column) {
+            (Ok(line), Ok(column)) => self.jump_to(Position {
+                x: column.saturating_sub(1),
+                y: line
+                    .saturating_sub(1)
+                    .min(self.document.len().saturating_sub(1)),
+            }),
+            _ => self.status_message = StatusMessage::from(format!("Not a line number: {}", input)),
+        }
+        Ok(())
+    }
+
+    //F7: the next word not in the dictionary, wrapping around at the end of the document
+    fn next_misspelling(&mut self) {
+        if self.spell.is_none() {
+            self.status_message =
+                StatusMessage::from("No dictionary (set spell.dictionary in config)".to_string());
+            return;
+        }
+        let Position { x, y } = self.cursor_position;
+        let len = self.document.len();
+        for step in 0..=len {
+            let line = (y + step) % len.max(1);
+            let row = match self.document.row(line) {
+                Some(row) => row,
+                None => break,
+            };
+            let found = self
+                .misspellings(row)
+                .into_iter()
+                .find(|word| (step > 0 || word.start > x) && (step < len || word.start <= x));
+            if let Some(word) = found {
+                self.jump_to(Position {
+                    x: word.start,
+                    y: line,
+                });
+                return;
+            }
+        }
+        self.status_message = StatusMessage::from("No misspellings".to_string());
+    }
+
+    //F8: replaces the misspelled word at the cursor with a suggestion,
+    //or adds it to the personal dictionary
+    fn correct_word(&mut self) -> Result<(), std::io::Error> {
+        if self.spell.is_none() {
+            self.status_message =
+                StatusMessage::from("No dictionary (set spell.dictionary in config)".to_string());
+            return Ok(());
+        }
+        let Position { x, y } = self.cursor_position;
+        let word = self.document.row(y).and_then(|row| {
+            self.misspellings(row)
+                .into_iter()
+                .find(|word| word.start <= x && x <= word.end)
+                .map(|word| (row.substring(word.start, word.end), word))
+        });
+        let (text, range) = match word {
+            Some(word) => word,
+            None => {
+                self.status_message =
+                    StatusMessage::from("No misspelled word at the cursor".to_string());
+                return Ok(());
+            }
+        };
+        let suggestions = self
+            .spell
+            .as_ref()
+            .map_or_else(Vec::new, |spell| spell.suggestions(&text));
+        let choices: Vec<String> = suggestions
+            .iter()
+            .enumerate()
+            .map(|(i, suggestion)| format!("{} {}", i + 1, suggestion))
+            .collect();
+        let prompt = if choices.is_empty() {
+            format!(
+                "No suggestions for {}, a to add it to the dictionary: ",
+                text
+            )
+        } else {
+            format!(
+                "Replace {} with {}, or a to add it: ",
+                text,
+                choices.join(", ")
+            )
+        };
+        let answer = match self.prompt(&prompt, |_, _, _| {})? {
+            Some(answer) => answer,
+            None => return Ok(()),
+        };
+        if answer.trim() == "a" {
+            if let Some(spell) = &mut self.spell {
+                match spell.add_word(&text) {
+                    Ok(()) => self.status_message = StatusMessage::from(format!("Added {}", text)),
+                    Err(error) => {
+                        self.status_message =
+                            StatusMessage::from(format!("Could not add {}: {}", text, error))
+                    }
+                }
+            }
+            return Ok(());
+        }
+        let replacement = match answer.trim().parse::<usize>() {
+            Ok(choice) if (1..=suggestions.len()).contains(&choice) => &suggestions[choice - 1],
+            _ => {
+                self.status_message = StatusMessage::from(format!("Invalid choice: {}", answer));
+                return Ok(());
+            }
+        };
+        if self.document.is_read_only() {
+            self.status_message = StatusMessage::from("Buffer is read-only".to_string());
+            return Ok(());
+        }
+        let start = Position { x: range.start, y };
+        self.begin_edit(None);
+        self.document
+            .delete_between(&start, &Position { x: range.end, y });
+        self.cursor_position = self.document.insert_text(&start, replacement);
+        Ok(())
+    }
+
+    //Alt-v: switches the selection between running text and a block, starting one at the cursor
+    fn toggle_block_selection(&mut self) {
+        if self.selection_anchor.is_none() {
+            self.selection_anchor = Some(self.cursor_position);
+            self.block_selection = true;
+        } else {
+            self.block_selection = !self.block_selection;
+        }
+    }
+
+    //replaces the block with `c` on every line, a block of no width just gets `c` inserted
+    fn type_in_block(&mut self, c: char) {
+        let (lines, left, right) = match self.block() {
+            Some(block) => block,
+            None => return,
+        };
+        self.begin_edit(Some(UndoGroup::Typing));
+        if right > left {
+            self.document.delete_block(lines.clone(), left, right);
+        }
+        let text = c.to_string();
+        let width = Row::from(&text[..]).width();
+        self.document
+            .insert_block(lines.start, left, &vec![text; lines.len()]);
+        self.move_block_to_column(left + width);
+    }
+
+    //deletes the block, or the column before (Backspace) or after (Delete) a block of no width
+    fn delete_in_block(&mut self, key: Key) {
+        let (lines, mut left, mut right) = match self.block() {
+            Some(block) => block,
+            None => return,
+        };
+        if left == right {
+            let row = self.document.row(self.cursor_position.y);
+            if key == Key::Backspace {
+                let index = row.map_or(0, |row| row.index_at_column(left));
+                if index == 0 {
+                    return;
+                }
+                left = row.map_or(0, |row| row.display_column(index - 1));
+            } else {
+                let index = row.map_or(0, |row| row.index_at_column(left));
+                right = row
+                    .map_or(0, |row| row.display_column(index + 1))
+                    .max(left + 1);
+            }
+        }
+        self.begin_edit(Some(UndoGroup::Deleting));
+        self.document.delete_block(lines, left, right);
+        self.move_block_to_column(left);
+    }
+
+    //collapses the block to screen column `column` on the lines it spans
+    fn move_block_to_column(&mut self, column: usize) {
+        let index_at =
+            |document: &Document, y| document.row(y).map_or(0, |row| row.index_at_column(column));
+        if let Some(anchor) = &mut self.selection_anchor {
+            anchor.x = index_at(&self.document, anchor.y);
+        }
+        self.cursor_position.x = index_at(&self.document, self.cursor_position.y);
+    }
+
+    //puts the selection on the clipboard, returns false if nothing is selected
+    fn copy(&mut self) -> bool {
+        let clipboard = match (self.block(), self.selection()) {
+            (Some((lines, left, right)), _) if right > left => Clipboard {
+                lines: self.document.block_text(lines, left, right),
+                block: true,
+            },
+            (None, Some((start, end))) => Clipboard {
+                lines: self
+                    .document
+                    .text_between(&start, &end)
+                    .split('\n')
+                    .map(String::from)
+                    .collect(),
+                block: false,
+            },
+            _ => {
+                self.status_message = StatusMessage::from("Nothing selected".to_string());
+                return false;
+            }
+        };
+        self.clipboard = Some(clipboard);
+        true
+    }
+
+    fn cut(&mut self) {
+        if !self.copy() {
+            return;
+        }
+        self.begin_edit(None);
+        if let Some((lines, left, right)) = self.block() {
+            self.document.delete_block(lines, left, right);
+            self.move_block_to_column(left);
+        } else if let Some((start, end)) = self.selection() {
+            self.document.delete_between(&start, &end);
+            self.cursor_position = start;
+            self.selection_anchor = None;
+        }
+    }
+
+    //a block goes in at the cursor's screen column on the lines from the cursor down
+    fn paste(&mut self) {
+        let (lines, block) = match &self.clipboard {
+            Some(clipboard) => (clipboard.lines.clone(), clipboard.block),
+            None => {
+                self.status_message = StatusMessage::from("Clipboard is empty".to_string());
+                return;
+            }
+        };
+        self.begin_edit(None);
+        self.selection_anchor = None;
+        if block {
+            let Position { x, y } = self.cursor_position;
+            let column = self.document.row(y).map_or(0, |row| row.display_column(x));
+            self.document.insert_block(y, column, &lines);
+            let width = lines.first().map_or(0, |line| Row::from(&line[..]).width());
+            self.cursor_position.x = self
+                .document
+                .row(y)
+                .map_or(0, |row| row.index_at_column(column + width));
+        } else {
+            self.cursor_position = self
+                .document
+                .insert_text(&self.cursor_position, &lines.join("\n"));
+        }
+    }
+
+    //offers the words of all open documents that start with the word left of the cursor
+    fn start_completion(&mut self) {
+        let before: String = match self.document.row(self.cursor_position.y) {
+            Some(row) => row
+                .as_str()
+                .graphemes(true)
+                .take(self.cursor_position.x)
+                .collect(),
+            None => String::new(),
+        };
+        let prefix = completion::prefix_before(&before);
+        let current: Vec<&str> = self.document.lines().collect();
+        let others: Vec<&str> = self
+            .buffers
+            .iter()
+            .flat_map(|buffer| buffer.document.lines())
+            .collect();
+        let candidates = completion::rank(prefix, self.cursor_position.y, &current, &others);
+        if candidates.is_empty() {
+            self.status_message = StatusMessage::from("No completions".to_string());
+        } else {
+            self.completion = Some(Completion::new(candidates, prefix));
+        }
+    }
+
+    //keys while the completion popup is open, returns false if the key closed it and still needs handling
+    fn process_completion_key(&mut self, key: Key) -> bool {
+        let completion = match &mut self.completion {
+            Some(completion) => completion,
+            None => return false,
+        };
+        match key {
+            Key::Down | Key::Ctrl('n') => completion.select_next(),
+            Key::Up | Key::Ctrl('p') => completion.select_previous(),
+            Key::Char('\n') | Key::Char('\t') => {
+                let remainder = completion.remainder().to_string();
+                self.completion = None;
+                self.begin_edit(Some(UndoGroup::Typing));
+                for c in remainder.chars() {
+                    self.document.insert(&self.cursor_position, c);
+                    self.move_cursor(Key::Right);
+                }
+            }
+            Key::Esc => self.completion = None,
+            _ => {
+                self.completion = None;
+                return false;
+            }
+        }
+        true
+    }
+
+    //takes an undo checkpoint unless this edit continues a run of the same kind
+    fn begin_edit(&mut self, group: Option<UndoGroup>) {
+        if group.is_none() || group != self.undo_group {
+            self.document.checkpoint(&self.cursor_position);
+        }
+        self.undo_group = group;
+    }
+
+    fn restore_cursor(&mut self, position: Position) {
+        self.undo_group = None;
+        self.selection_anchor = None;
+        self.cursor_position = position;
+        self.clamp_cursor();
+    }
+
+    //keeps the cursor inside the document after its contents were replaced
+    fn clamp_cursor(&mut self) {
+        let Position { mut x, mut y } = self.cursor_position;
+        y = y.min(self.document.len());
+        let width = self.document.row(y).map_or(0, Row::len);
+        x = x.min(width);
+        self.cursor_position = Position { x, y };
+    }
+
+    fn revert_hunk(&mut self) -> Option<usize> {
+        self.begin_edit(None);
+        self.document.revert_hunk(self.cursor_position.y)
+    }
+
+    //runs the formatter configured for the file type, if any, then writes the file
+    //a document read from stdin has no name yet, so one is asked for first
+    fn save(&mut self) -> Result<(), std::io::Error> {
+        if self.document.file_name.is_none() {
+            match self.prompt("Save as: ", |_, _, _| {})? {
+                Some(name) if !name.trim().is_empty() => self.document.set_file_name(name.trim()),
+                _ => {
+                    self.status_message = StatusMessage::from("Save aborted".to_string());
+                    return Ok(());
+                }
+            }
+        }
+        let file_type = self.document.file_type();
+        let formatter = self
+            .config
+            .get(&format!("formatter.{}", file_type.key()))
+            .map(String::from);
+        let mut formatter_error = None;
+        if let Some(command) = formatter {
+            if let Err(error) = self.replace_lines_with_command(&command, 0..self.document.len()) {
+                formatter_error = Some(error);
+            }
+        }
+        let saved = self.document.save();
+        //trimming trailing whitespace may have shortened the cursor's line
+        self.clamp_cursor();
+        let message = match (saved, formatter_error) {
+            (Ok(()), None) => "File saved successfully".to_string(),
+            (Ok(()), Some(error)) => format!("File saved, but formatting failed: {}", error),
+            (Err(error), _) => format!("Unable to save file: {}", error),
+        };
+        self.status_message = StatusMessage::from(message);
+        Ok(())
+    }
+
+    //pipes the selected lines, or the whole document, through a shell command
+    fn filter_selection(&mut self) -> Result<(), std::io::Error> {
+        let history = self.session.command_history().to_vec();
+        let command = match self.prompt_with_history(
+            "Filter through command (Ctrl-P/Ctrl-N for history): ",
+            history,
+            |_, _, _| {},
+        )? {
+            Some(command) if !command.trim().is_empty() => command,
+            _ => return Ok(()),
+        };
+        self.session.add_command(&command);
+        let lines = self.selected_lines().unwrap_or(0..self.document.len());
+        if let Err(error) = self.replace_lines_with_command(&command, lines) {
+            self.status_message = StatusMessage::from(error);
+        }
+        Ok(())
+    }
+
+    //swaps `lines` for the output of `command` fed with them, as a single undoable edit
+    fn replace_lines_with_command(
+        &mut self,
+        command: &str,
+        lines: std::ops::Range<usize>,
+    ) -> Result<(), String> {
+        let replaced = match self.document.filter_rows(command, lines, &self.cursor_position)? {
+            Some(replaced) => replaced,
+            None => return Ok(()),
+        };
+        //the filter took its own checkpoint, whatever comes next is a new edit
+        self.undo_group = None;
+        if self.selection_anchor.is_some() {
+            self.selection_anchor = Some(Position {
+                x: 0,
+                y: replaced.start,
+            });
+            self.cursor_position = Position {
+                x: 0,
+                y: replaced.end,
+            };
+        }
+        self.clamp_cursor();
+        Ok(())
+    }
+
+    //single key commands of the pager, returns false for keys it leaves to the normal handling
+    fn process_view_key(&mut self, key: Key) -> Result<bool, std::io::Error> {
+        match key {
+            Key::Char('q') => self.quit(),
+            Key::Char('/') => self.search()?,
+            Key::Char('n') => self.repeat_search(true),
+            Key::Char('N') => self.repeat_search(false),
+            Key::Char(' ') => {
+                self.document
+                    .bookmarks_mut()
+                    .record_jump(self.cursor_position);
+                self.move_cursor(Key::PageDown);
+            }
+            Key::Char('b') => {
+                self.document
+                    .bookmarks_mut()
+                    .record_jump(self.cursor_position);
+                self.move_cursor(Key::PageUp);
+            }
+            Key::Char('g') => self.jump_to(Position::default()),
+            Key::Char('G') => self.jump_to(Position {
+                x: 0,
+                y: self.document.len().saturating_sub(1),
+            }),
+            Key::Char(_) | Key::Delete | Key::Backspace | Key::Ctrl('s') => {}
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn repeat_search(&mut self, forward: bool) {
+        let query = match &self.last_search {
+            Some(query) => query.clone(),
+            None => return,
+        };
+        //step off the current match first so it is not found again
+        let mut at = self.cursor_position;
+        if forward {
+            at.x = at.x.saturating_add(1);
+        }
+        match self.document.find(&query, &at, forward) {
+            Some(position) => self.jump_to(position),
+            None => {
+                self.status_message = StatusMessage::from(format!("Pattern not found: {}", query))
+            }
+        }
+    }
+
+    //replayed keys come first, keys typed while recording a macro are captured
+    fn read_key(&mut self) -> Result<Key, std::io::Error> {
+        if let Some(key) = self.replay.next_key() {
+            return Ok(key);
+        }
+        let key = self.terminal.read_key()?;
+        self.macros.record(key);
+        Ok(key)
+    }
+
+    fn toggle_recording(&mut self) -> Result<(), std::io::Error> {
+        if let Some((register, len)) = self.macros.stop_recording() {
+            let message = match self.macros.save() {
+                Ok(()) => format!("Recorded {} keys into register {}", len, register),
+                Err(_) => format!(
+                    "Recorded {} keys into register {}, but could not save them",
+                    len, register
+                ),
+            };
+            self.status_message = StatusMessage::from(message);
+            return Ok(());
+        }
+        if let Some(register) = self.prompt("Record macro into register (a-z): ", |_, _, _| {})? {
+            match register.chars().next() {
+                Some(register) if register.is_ascii_lowercase() => {
+                    self.macros.start_recording(register);
+                    self.status_message = StatusMessage::from(format!(
+                        "Recording into register {}, Ctrl-R to stop",
+                        register
+                    ));
+                }
+                _ => self.status_message = StatusMessage::from("Invalid register".to_string()),
+            }
+        }
+        Ok(())
+    }
+
+    //asks for a register with an optional count in front, e.g. `a` or `10a`
+    fn replay_macro(&mut self) -> Result<(), std::io::Error> {
+        let input = match self.prompt("Replay macro ([count]register): ", |_, _, _| {})? {
+            Some(input) => input,
+            None => return Ok(()),
+        };
+        let input = input.trim();
+        let digits = input.chars().take_while(char::is_ascii_digit).count();
+        let count = if digits == 0 {
+            Some(1)
+        } else {
+            input[..digits].parse::<usize>().ok()
+        };
+        let mut register = input[digits..].chars();
+        let (count, register) = match (count, register.next(), register.next()) {
+            (Some(count), Some(register), None) => (count, register),
+            _ => {
+                self.status_message = StatusMessage::from("Invalid macro".to_string());
+                return Ok(());
+            }
+        };
+        let keys = match self.macros.get(register) {
+            Some(keys) => keys,
+            None => {
+                self.status_message =
+                    StatusMessage::from(format!("Register {} is empty", register));
+                return Ok(());
+            }
+        };
+        self.replay.queue(keys, count);
+        Ok(())
+    }
+
+    fn prompt<C>(&mut self, prompt: &str, callback: C) -> Result<Option<String>, std::io::Error>
+    where
+        C: FnMut(&mut Self, Key, &String),
+    {
+        self.prompt_with_history(prompt, Vec::new(), callback)
+    }
+
+    //like prompt, with Ctrl-P/Ctrl-N stepping through earlier answers, oldest first in `history`
+    fn prompt_with_history<C>(
+        &mut self,
+        prompt: &str,
+        history: Vec<String>,
+        mut callback: C,
+    ) -> Result<Option<String>, std::io::Error>
+    where
+        C: FnMut(&mut Self, Key, &String),
+    {
+        let mut result = String::new();
+        //history.len() stands for what was being typed before going back in the history
+        let mut history_index = history.len();
+        let mut typed = String::new();
+        loop {
+            self.status_message = StatusMessage::from(format!("{}{}", prompt, result));
+            self.refresh_screen()?;
+            let key = self.read_key()?;
+            match key {
+                Key::Backspace => {
+                    result.pop();
+                }
+                Key::Char('\n') => break,
+                Key::Char(c) if !c.is_control() => result.push(c),
+                Key::Ctrl('p') if history_index > 0 => {
+                    if history_index == history.len() {
+                        typed = result.clone();
+                    }
+                    history_index -= 1;
+                    result = history[history_index].clone();
+                }
+                Key::Ctrl('n') if history_index < history.len() => {
+                    history_index += 1;
+                    result = history.get(history_index).unwrap_or(&typed).clone();
+                }
+                Key::Esc => {
+                    self.status_message = StatusMessage::from(String::new());
+                    return Ok(None);
+                }
+                _ => (),
+            }
+            callback(self, key, &result);
+        }
+        self.status_message = StatusMessage::from(String::new());
+        Ok(Some(result))
+    }
+
+    fn search(&mut self) -> Result<(), std::io::Error> {
+        let old_position = self.cursor_position;
+        let mut forward = true;
+        let history = self.session.search_history().to_vec();
+        let query = self.prompt_with_history(
+            "Search (Esc to cancel, arrows to navigate, Ctrl-P/Ctrl-N for history): ",
+            history,
+            |editor, key, query| {
+                let mut moved = false;
+                match key {
+                    Key::Right | Key::Down => {
+                        forward = true;
+                        editor.move_cursor(Key::Right);
+                        moved = true;
+                    }
+                    Key::Left | Key::Up => forward = false,
+                    //scanning a large file on every keystroke is too slow, only search when asked to
+                    _ if editor.document.is_large() => return,
+                    _ => forward = true,
+                }
+                if let Some(position) =
+                    editor
+                        .document
+                        .find(query, &editor.cursor_position, forward)
+                {
+                    editor.cursor_position = position;
+                    editor.scroll();
+                } else if moved {
+                    editor.move_cursor(Key::Left);
+                }
+            },
+        )?;
+        let query = match query {
+            Some(query) => query,
+            None => {
+                self.cursor_position = old_position;
+                self.scroll();
+                return Ok(());
+            }
+        };
+        if self.document.is_large() && self.cursor_position == old_position {
+            match self.document.find(&query, &self.cursor_position, true) {
+                Some(position) => self.cursor_position = position,
+                None => self.status_message = StatusMessage::from(format!("Not found: {}", query)),
+            }
+            self.scroll();
+        }
+        if self.cursor_position != old_position {
+            self.document.bookmarks_mut().record_jump(old_position);
+        }
+        self.session.add_search(&query);
+        self.last_search = Some(query);
+        Ok(())
+    }
+
+    fn find_file(&mut self) -> Result<(), std::io::Error> {
+        self.finder = Some(Finder::new(Path::new(".")));
+        let query = self.prompt("Open file (Up/Down to choose): ", |editor, key, query| {
+            if let Some(finder) = &mut editor.finder {
+                match key {
+                    Key::Up => finder.select_previous(),
+                    Key::Down => finder.select_next(),
+                    _ => finder.update(query),
+                }
+            }
+        })?;
+        let selection = self
+            .finder
+            .take()
+            .and_then(|finder| finder.selection().map(String::from));
+        if query.is_some() {
+            match selection {
+                Some(path) => self.open_file(&path),
+                None => self.status_message = StatusMessage::from("No matching file".to_string()),
+            }
+        }
+        Ok(())
+    }
+
+    //shows `path` in the editor, switching to it if it is already open in another buffer
+    fn open_file(&mut self, path: &str) {
+        if self.document.is_file(Path::new(path)) {
+            return;
+        }
+        if let Some(index) = self
+            .buffers
+            .iter()
+            .position(|buffer| buffer.document.is_file(Path::new(path)))
+        {
+            let buffer = self.buffers.remove(index);
+            self.show_buffer(buffer);
+            return;
+        }
+        match Document::open(path) {
+            Ok(document) => {
+                let buffer = self.restored_buffer(document);
+                self.show_buffer(buffer);
+            }
+            Err(_) => {
+                self.status_message =
+                    StatusMessage::from(format!("Error: Could not open file {}", path))
+            }
+        }
+    }
+
+    //puts `buffer` on screen and stashes the current one at the end of the buffer list
+    fn show_buffer(&mut self, buffer: Buffer) {
+        let previous = self.replace_buffer(buffer);
+        //an untouched [No Name] document is not worth keeping around
+        if previous.document.file_name.is_some() || !previous.document.is_empty() {
+            self.buffers.push(previous);
+        }
+    }
+
+    fn replace_buffer(&mut self, buffer: Buffer) -> Buffer {
+        Buffer {
+            document: std::mem::replace(&mut self.document, buffer.document),
+            cursor_position: std::mem::replace(&mut self.cursor_position, buffer.cursor_position),
+            offset: std::mem::replace(&mut self.offset, buffer.offset),
+        }
+    }
+
+    fn next_buffer(&mut self) {
+        if self.buffers.is_empty() {
+            self.status_message = StatusMessage::from("No other buffers".to_string());
+            return;
+        }
+        let next = self.buffers.remove(0);
+        let previous = self.replace_buffer(next);
+        self.buffers.push(previous);
+    }
+
+    fn previous_buffer(&mut self) {
+        if let Some(previous) = self.buffers.pop() {
+            let current = self.replace_buffer(previous);
+            self.buffers.insert(0, current);
+        } else {
+            self.status_message = StatusMessage::from("No other buffers".to_string());
+        }
+    }
+
+    //Enter on a directory listing: descend into directories, open files in a new buffer
+    fn open_directory_entry(&mut self) {
+        let path = match self.document.directory_entry(self.cursor_position.y) {
+            Some(path) => path,
+            None => return,
+        };
+        let name = path.to_string_lossy().to_string();
+        if path.is_dir() {
+            match Document::open_directory(&name) {
+                Ok(document) => {
+                    self.document = document;
+                    self.cursor_position = Position::default();
+                    self.offset = Position::default();
+                }
+                Err(_) => {
+                    self.status_message =
+                        StatusMessage::from(format!("Error: Could not open directory {}", name))
+                }
+            }
+        } else {
+            self.open_file(&name);
+        }
+    }
+
     fn move_cursor(&mut self, key: Key) {
         let terminal_height = self.terminal.size().height as usize;
         let Position { mut x, mut y } = self.cursor_position;
@@ -222,11 +1761,7 @@ impl Editor {
         };
         match key {
             Key::Up => y = y.saturating_sub(1),
-            Key::Down => {
-                if y < height {
-                    y = y.saturating_add(1);
-                }
-            }
+            Key::Down if y < height => y = y.saturating_add(1),
             Key::Left => {
                 if x > 0 {
                     x -= 1
@@ -249,18 +1784,12 @@ impl Editor {
             }
             Key::PageDown => {
                 y = if y.saturating_add(terminal_height) < height {
-                    y + terminal_height as usize
+                    y + terminal_height
                 } else {
                     height
                 }
             }
-            Key::PageUp => {
-                y = if y > terminal_height {
-                    y - terminal_height
-                } else {
-                    0
-                }
-            }
+            Key::PageUp => y = y.saturating_sub(terminal_height),
             Key::Home => x = 0,
             Key::End => x = width,
             _ => (),
@@ -279,7 +1808,7 @@ impl Editor {
 
     fn scroll(&mut self) {
         let Position { x, y } = self.cursor_position;
-        let width = self.terminal.size().width as usize;
+        let width = self.text_width();
         let height = self.terminal.size().height as usize;
 
         let offset = &mut self.offset;
@@ -294,6 +1823,9 @@ impl Editor {
         } else if x >= offset.x.saturating_add(width) {
             offset.x = x.saturating_sub(width).saturating_add(1);
         }
+        //a screen worth of rows either side, so moving the cursor off screen still finds its row
+        let start = offset.y.saturating_sub(height);
+        self.document.page_in(start, height.saturating_mul(3));
     }
 }
 
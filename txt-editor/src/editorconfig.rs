@@ -0,0 +1,275 @@
+use crate::document::LineEnding;
+use crate::encoding::Encoding;
+use crate::finder::glob_match;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const EDITORCONFIG_FILE: &str = ".editorconfig";
+//`{1..100000}` would otherwise expand into that many patterns
+const MAX_EXPANSIONS: usize = 1000;
+//how many columns one level of indentation takes when nothing is configured
+const DEFAULT_INDENT_WIDTH: usize = 4;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum IndentStyle {
+    Tab,
+    Space,
+}
+
+//the EditorConfig properties that apply to one file, None where nothing says otherwise
+#[derive(Default, Clone, PartialEq, Debug)]
+pub struct Settings {
+    pub indent_style: Option<IndentStyle>,
+    pub indent_size: Option<usize>,
+    pub tab_width: Option<usize>,
+    pub end_of_line: Option<LineEnding>,
+    pub charset: Option<Encoding>,
+    pub insert_final_newline: Option<bool>,
+    pub trim_trailing_whitespace: Option<bool>,
+}
+
+//one .editorconfig file: whether it stops the search upwards, and its sections in order
+struct EditorConfig {
+    root: bool,
+    sections: Vec<(String, Vec<(String, String)>)>,
+}
+
+impl Settings {
+    //reads the .editorconfig files from the file's directory up to the first one marked root,
+    //closer files and later sections winning
+    pub fn for_file(path: &Path) -> Self {
+        let path = path
+            .canonicalize()
+            .or_else(|_| std::path::absolute(path))
+            .unwrap_or_else(|_| path.to_path_buf());
+        let mut configs: Vec<(PathBuf, EditorConfig)> = Vec::new();
+        for dir in path.ancestors().skip(1) {
+            if let Ok(contents) = fs::read_to_string(dir.join(EDITORCONFIG_FILE)) {
+                let config = EditorConfig::parse(&contents);
+                let root = config.root;
+                configs.push((dir.to_path_buf(), config));
+                if root {
+                    break;
+                }
+            }
+        }
+        let mut settings = Self::default();
+        for (dir, config) in configs.iter().rev() {
+            let relative = match path.strip_prefix(dir) {
+                Ok(relative) => relative.to_string_lossy().replace('\\', "/"),
+                Err(_) => continue,
+            };
+            for (pattern, properties) in &config.sections {
+                if section_matches(pattern, &relative) {
+                    for (key, value) in properties {
+                        settings.set(key, value);
+                    }
+                }
+            }
+        }
+        settings
+    }
+
+    pub fn indent_width(&self) -> usize {
+        self.indent_size
+            .or(self.tab_width)
+            .unwrap_or(DEFAULT_INDENT_WIDTH)
+    }
+
+    fn set(&mut self, key: &str, value: &str) {
+        match key {
+            "indent_style" => {
+                self.indent_style = match value {
+                    "tab" => Some(IndentStyle::Tab),
+                    "space" => Some(IndentStyle::Space),
+                    _ => None,
+                }
+            }
+            //`indent_size = tab` leaves it to tab_width
+            "indent_size" => self.indent_size = parse_size(value),
+            "tab_width" => self.tab_width = parse_size(value),
+            "end_of_line" => {
+                self.end_of_line = match value {
+                    "lf" => Some(LineEnding::Lf),
+                    "crlf" => Some(LineEnding::CrLf),
+                    "cr" => Some(LineEnding::Cr),
+                    _ => None,
+                }
+            }
+            "charset" => {
+                self.charset = match value {
+                    "utf-8" => Some(Encoding::Utf8),
+                    "utf-8-bom" => Some(Encoding::Utf8Bom),
+                    "utf-16le" => Some(Encoding::Utf16Le { bom: true }),
+                    "utf-16be" => Some(Encoding::Utf16Be { bom: true }),
+                    "latin1" => Some(Encoding::Latin1),
+                    _ => None,
+                }
+            }
+            "insert_final_newline" => self.insert_final_newline = parse_bool(value),
+            "trim_trailing_whitespace" => self.trim_trailing_whitespace = parse_bool(value),
+            _ => (),
+        }
+    }
+}
+
+impl EditorConfig {
+    fn parse(contents: &str) -> Self {
+        let mut config = Self {
+            root: false,
+            sections: Vec::new(),
+        };
+        for line in contents.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if let Some(pattern) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                config.sections.push((pattern.to_string(), Vec::new()));
+                continue;
+            }
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim().to_lowercase(), value.trim().to_lowercase()),
+                None => continue,
+            };
+            match config.sections.last_mut() {
+                Some((_, properties)) => properties.push((key, value)),
+                None if key == "root" => config.root = value == "true",
+                None => (),
+            }
+        }
+        config
+    }
+}
+
+fn parse_size(value: &str) -> Option<usize> {
+    value.parse().ok().filter(|size| *size > 0)
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
+
+//a pattern without a slash matches the file name in any directory,
+//one with a slash is relative to the directory of the .editorconfig file
+fn section_matches(pattern: &str, relative: &str) -> bool {
+    let pattern = if pattern.contains('/') {
+        pattern.trim_start_matches('/').to_string()
+    } else {
+        format!("**/{}", pattern)
+    };
+    expand_braces(&pattern)
+        .iter()
+        .any(|pattern| glob_match(pattern, relative))
+}
+
+//`{a,b}` alternatives and `{1..3}` number ranges, turned into one plain glob per combination
+fn expand_braces(pattern: &str) -> Vec<String> {
+    let open = match pattern.find('{') {
+        Some(open) => open,
+        None => return vec![pattern.to_string()],
+    };
+    let mut depth = 0;
+    let mut close = None;
+    let mut commas = Vec::new();
+    for (i, c) in pattern[open..].char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    close = Some(open + i);
+                    break;
+                }
+            }
+            ',' if depth == 1 => commas.push(open + i),
+            _ => (),
+        }
+    }
+    let close = match close {
+        Some(close) => close,
+        None => return vec![pattern.to_string()],
+    };
+    let inner = &pattern[open + 1..close];
+    let alternatives: Vec<String> = if !commas.is_empty() {
+        let mut start = open + 1;
+        let mut alternatives = Vec::new();
+        for comma in commas.into_iter().chain(std::iter::once(close)) {
+            alternatives.extend(expand_braces(&pattern[start..comma]));
+            start = comma + 1;
+        }
+        alternatives
+    } else if let Some(range) = number_range(inner) {
+        range
+    } else {
+        //a lone `{word}` is matched literally
+        vec![format!("{{{}}}", inner)]
+    };
+    let (before, after) = (&pattern[..open], expand_braces(&pattern[close + 1..]));
+    let mut expanded = Vec::new();
+    for alternative in &alternatives {
+        for rest in &after {
+            if expanded.len() == MAX_EXPANSIONS {
+                return expanded;
+            }
+            expanded.push(format!("{}{}{}", before, alternative, rest));
+        }
+    }
+    expanded
+}
+
+fn number_range(inner: &str) -> Option<Vec<String>> {
+    let (from, to) = inner.split_once("..")?;
+    let (from, to): (i64, i64) = (from.parse().ok()?, to.parse().ok()?);
+    let (low, high) = (from.min(to), from.max(to));
+    if high - low >= MAX_EXPANSIONS as i64 {
+        return None;
+    }
+    Some((low..=high).map(|n| n.to_string()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_braces_and_matches_sections() {
+        assert_eq!(expand_braces("*.{rs,toml}"), ["*.rs", "*.toml"]);
+        assert_eq!(expand_braces("v{1..3}"), ["v1", "v2", "v3"]);
+        assert_eq!(expand_braces("{a,{b,c}}x"), ["ax", "bx", "cx"]);
+        assert_eq!(expand_braces("{word}"), ["{word}"]);
+        assert!(section_matches("*.rs", "src/main.rs"));
+        assert!(section_matches("src/*.rs", "src/main.rs"));
+        assert!(!section_matches("/*.rs", "src/main.rs"));
+        assert!(section_matches("{Makefile,*.mk}", "build/rules.mk"));
+    }
+
+    #[test]
+    fn closer_files_and_later_sections_win() {
+        let dir = std::env::temp_dir().join(format!("editorconfig-{}", std::process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(
+            dir.join(EDITORCONFIG_FILE),
+            "root = true\n[*]\nindent_style = space\nindent_size = 4\nend_of_line = crlf\n\
+             [*.md]\ntrim_trailing_whitespace = false\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("sub").join(EDITORCONFIG_FILE),
+            "[*]\nindent_style = tab\ncharset = latin1\n; comment\n[*.md]\nindent_size = 2\n",
+        )
+        .unwrap();
+
+        let settings = Settings::for_file(&dir.join("sub").join("notes.md"));
+        assert_eq!(settings.indent_style, Some(IndentStyle::Tab));
+        assert_eq!(settings.indent_width(), 2);
+        assert_eq!(settings.end_of_line, Some(LineEnding::CrLf));
+        assert_eq!(settings.charset, Some(Encoding::Latin1));
+        assert_eq!(settings.trim_trailing_whitespace, Some(false));
+        assert_eq!(settings.insert_final_newline, None);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
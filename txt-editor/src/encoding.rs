@@ -0,0 +1,221 @@
+//how many leading bytes are looked at when guessing what a file contains
+const SAMPLE_SIZE: usize = 8192;
+const HEX_BYTES_PER_ROW: usize = 16;
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Utf8Bom,
+    Utf16Le {
+        bom: bool,
+    },
+    Utf16Be {
+        bom: bool,
+    },
+    Latin1,
+}
+
+pub enum Contents {
+    Text(String, Encoding),
+    Binary,
+}
+
+impl Encoding {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Utf8 => "UTF-8",
+            Self::Utf8Bom => "UTF-8 BOM",
+            Self::Utf16Le { .. } => "UTF-16LE",
+            Self::Utf16Be { .. } => "UTF-16BE",
+            Self::Latin1 => "Latin-1",
+        }
+    }
+
+    //turns text back into bytes, fails with the first character the encoding cannot represent
+    pub fn encode(self, text: &str) -> Result<Vec<u8>, char> {
+        let bytes = match self {
+            Self::Utf8 => text.as_bytes().to_vec(),
+            Self::Utf8Bom => {
+                let mut bytes = vec![0xEF, 0xBB, 0xBF];
+                bytes.extend_from_slice(text.as_bytes());
+                bytes
+            }
+            Self::Utf16Le { bom } | Self::Utf16Be { bom } => {
+                let big_endian = matches!(self, Self::Utf16Be { .. });
+                let units = bom.then_some(0xFEFF).into_iter().chain(text.encode_utf16());
+                units
+                    .flat_map(|unit| {
+                        if big_endian {
+                            unit.to_be_bytes()
+                        } else {
+                            unit.to_le_bytes()
+                        }
+                    })
+                    .collect()
+            }
+            Self::Latin1 => {
+                let mut bytes = Vec::with_capacity(text.len());
+                for c in text.chars() {
+                    bytes.push(u8::try_from(u32::from(c)).map_err(|_| c)?);
+                }
+                bytes
+            }
+        };
+        Ok(bytes)
+    }
+}
+
+pub fn decode(bytes: &[u8]) -> Contents {
+    if let Some(rest) = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        return Contents::Text(String::from_utf8_lossy(rest).to_string(), Encoding::Utf8Bom);
+    }
+    if let Some(rest) = bytes.strip_prefix(&[0xFF, 0xFE]) {
+        return Contents::Text(decode_utf16(rest, false), Encoding::Utf16Le { bom: true });
+    }
+    if let Some(rest) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        return Contents::Text(decode_utf16(rest, true), Encoding::Utf16Be { bom: true });
+    }
+    let sample = &bytes[..bytes.len().min(SAMPLE_SIZE)];
+    if let Some(big_endian) = guess_utf16(sample) {
+        let encoding = if big_endian {
+            Encoding::Utf16Be { bom: false }
+        } else {
+            Encoding::Utf16Le { bom: false }
+        };
+        return Contents::Text(decode_utf16(bytes, big_endian), encoding);
+    }
+    if looks_binary(sample) {
+        return Contents::Binary;
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Contents::Text(text.to_string(), Encoding::Utf8),
+        //every byte is a valid Latin-1 character, so this always works and round trips
+        Err(_) => Contents::Text(
+            bytes.iter().map(|b| char::from(*b)).collect(),
+            Encoding::Latin1,
+        ),
+    }
+}
+
+fn decode_utf16(bytes: &[u8], big_endian: bool) -> String {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|pair| {
+            if big_endian {
+                u16::from_be_bytes([pair[0], pair[1]])
+            } else {
+                u16::from_le_bytes([pair[0], pair[1]])
+            }
+        })
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+//UTF-16 without a BOM shows up as mostly ASCII text with every other byte zero.
+//Returns whether it is big endian, or None if it does not look like UTF-16.
+fn guess_utf16(sample: &[u8]) -> Option<bool> {
+    if sample.len() < 4 || !sample.len().is_multiple_of(2) {
+        return None;
+    }
+    let pairs = sample.len() / 2;
+    let even_zeros = sample.iter().step_by(2).filter(|b| **b == 0).count();
+    let odd_zeros = sample
+        .iter()
+        .skip(1)
+        .step_by(2)
+        .filter(|b| **b == 0)
+        .count();
+    if odd_zeros * 10 >= pairs * 9 && even_zeros == 0 {
+        Some(false)
+    } else if even_zeros * 10 >= pairs * 9 && odd_zeros == 0 {
+        Some(true)
+    } else {
+        None
+    }
+}
+
+//NUL bytes or lots of control characters other than whitespace mean it is not text
+fn looks_binary(sample: &[u8]) -> bool {
+    if sample.contains(&0) {
+        return true;
+    }
+    let control = sample
+        .iter()
+        .filter(|b| **b < 0x20 && !matches!(**b, b'\n' | b'\r' | b'\t' | 0x0C | 0x1B))
+        .count();
+    control * 10 > sample.len()
+}
+
+//classic `hexdump -C` layout: offset, sixteen bytes in hex, then the printable ones
+pub fn hex_dump(bytes: &[u8]) -> Vec<String> {
+    bytes
+        .chunks(HEX_BYTES_PER_ROW)
+        .enumerate()
+        .map(|(index, chunk)| {
+            let mut line = format!("{:08x}  ", index * HEX_BYTES_PER_ROW);
+            for i in 0..HEX_BYTES_PER_ROW {
+                match chunk.get(i) {
+                    Some(byte) => line.push_str(&format!("{:02x} ", byte)),
+                    None => line.push_str("   "),
+                }
+                if i == HEX_BYTES_PER_ROW / 2 - 1 {
+                    line.push(' ');
+                }
+            }
+            line.push_str(" |");
+            for byte in chunk {
+                line.push(if byte.is_ascii_graphic() || *byte == b' ' {
+                    char::from(*byte)
+                } else {
+                    '.'
+                });
+            }
+            line.push('|');
+            line
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(bytes: &[u8], expected: &str, encoding: Encoding) {
+        match decode(bytes) {
+            Contents::Text(text, found) => {
+                assert_eq!(text, expected);
+                assert_eq!(found, encoding);
+                assert_eq!(found.encode(&text).unwrap(), bytes);
+            }
+            Contents::Binary => panic!("decoded as binary"),
+        }
+    }
+
+    #[test]
+    fn detects_and_round_trips_encodings() {
+        round_trip("héllo\n".as_bytes(), "héllo\n", Encoding::Utf8);
+        round_trip(b"\xEF\xBB\xBFhi", "hi", Encoding::Utf8Bom);
+        round_trip(b"\xFF\xFEh\0i\0", "hi", Encoding::Utf16Le { bom: true });
+        round_trip(b"\xFE\xFF\0h\0i", "hi", Encoding::Utf16Be { bom: true });
+        round_trip(
+            b"h\0e\0l\0l\0o\0",
+            "hello",
+            Encoding::Utf16Le { bom: false },
+        );
+        round_trip(b"caf\xE9 cr\xE8me", "café crème", Encoding::Latin1);
+    }
+
+    #[test]
+    fn binary_files_and_unencodable_text() {
+        assert!(matches!(
+            decode(b"\x7FELF\x02\x01\x01\0\0\0"),
+            Contents::Binary
+        ));
+        assert_eq!(Encoding::Latin1.encode("ok €"), Err('€'));
+        assert_eq!(
+            hex_dump(b"Hi\n"),
+            vec!["00000000  48 69 0a                                          |Hi.|"]
+        );
+    }
+}
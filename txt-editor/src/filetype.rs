@@ -0,0 +1,63 @@
+use std::path::Path;
+
+pub struct FileType {
+    name: &'static str,
+}
+
+impl Default for FileType {
+    fn default() -> Self {
+        Self { name: "Text" }
+    }
+}
+
+impl FileType {
+    pub fn from(file_name: &str) -> Self {
+        let path = Path::new(file_name);
+        let name = match path.file_name().and_then(|name| name.to_str()) {
+            Some("COMMIT_EDITMSG") | Some("MERGE_MSG") | Some("TAG_EDITMSG") => "Git Commit",
+            Some("Makefile") | Some("makefile") => "Makefile",
+            Some("Dockerfile") => "Dockerfile",
+            _ => match path.extension().and_then(|extension| extension.to_str()) {
+                Some("rs") => "Rust",
+                Some("toml") => "TOML",
+                Some("md") | Some("markdown") => "Markdown",
+                Some("py") => "Python",
+                Some("js") | Some("mjs") => "JavaScript",
+                Some("ts") => "TypeScript",
+                Some("json") => "JSON",
+                Some("c") | Some("h") => "C",
+                Some("cpp") | Some("cc") | Some("hpp") => "C++",
+                Some("go") => "Go",
+                Some("sh") | Some("bash") => "Shell",
+                Some("html") | Some("htm") => "HTML",
+                Some("css") => "CSS",
+                Some("yml") | Some("yaml") => "YAML",
+                _ => "Text",
+            },
+        };
+        Self { name }
+    }
+
+    pub fn name(&self) -> &str {
+        self.name
+    }
+
+    //text meant for people rather than a compiler, spell checked as a whole
+    pub fn is_prose(&self) -> bool {
+        matches!(self.name, "Markdown" | "Git Commit" | "Text")
+    }
+
+    //what starts a comment running to the end of the line
+    pub fn line_comment(&self) -> Option<&'static str> {
+        match self.name {
+            "Rust" | "JavaScript" | "TypeScript" | "C" | "C++" | "Go" => Some("//"),
+            "Python" | "Shell" | "TOML" | "YAML" | "Makefile" | "Dockerfile" => Some("#"),
+            _ => None,
+        }
+    }
+
+    //how the file type is spelled in config keys, e.g. `formatter.git-commit`
+    pub fn key(&self) -> String {
+        self.name.to_lowercase().replace(' ', "-")
+    }
+}
@@ -0,0 +1,50 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::thread;
+
+//pipes `input` through `sh -c command`, returning its output or, when it fails, what it had to say about it
+pub fn run(command: &str, input: &str) -> Result<String, String> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| format!("could not run {}: {}", command, error))?;
+
+    //written from another thread so a command that starts printing before it read everything cannot deadlock us
+    let mut stdin = child.stdin.take().ok_or("could not write to command")?;
+    let input = input.to_string();
+    let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+
+    let output = child
+        .wait_with_output()
+        .map_err(|error| format!("{} failed: {}", command, error))?;
+    //a command that exits without reading its input closes the pipe, that is not an error
+    let _ = writer.join();
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let message = stderr.lines().find(|line| !line.trim().is_empty());
+        Err(match message {
+            Some(message) => format!("{}: {}", command, message.trim()),
+            None => format!("{} exited with {}", command, output.status),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pipes_through_shell_commands() {
+        assert_eq!(run("sort", "b\nc\na\n"), Ok("a\nb\nc\n".to_string()));
+        assert_eq!(
+            run("echo oops >&2; exit 3", "ignored"),
+            Err("echo oops >&2; exit 3: oops".to_string())
+        );
+    }
+}
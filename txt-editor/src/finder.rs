@@ -0,0 +1,348 @@
+use std::fs;
+use std::path::Path;
+
+//stop walking once this many files were found, a fuzzy finder over more than that is not much use anyway
+const MAX_FILES: usize = 50_000;
+
+pub struct Finder {
+    files: Vec<String>,
+    //indexes into `files`, best match first
+    matches: Vec<usize>,
+    selected: usize,
+}
+
+impl Finder {
+    pub fn new(root: &Path) -> Self {
+        let mut files = Vec::new();
+        let mut rules = ancestor_rules(root);
+        walk(root, "", &mut rules, &mut files);
+        files.sort();
+        let matches = (0..files.len()).collect();
+        Self {
+            files,
+            matches,
+            selected: 0,
+        }
+    }
+
+    pub fn update(&mut self, query: &str) {
+        let mut scored: Vec<(i64, usize)> = self
+            .files
+            .iter()
+            .enumerate()
+            .filter_map(|(index, file)| fuzzy_score(query, file).map(|score| (score, index)))
+            .collect();
+        //best score first, shorter paths win ties
+        scored.sort_by(|a, b| {
+            b.0.cmp(&a.0)
+                .then(self.files[a.1].len().cmp(&self.files[b.1].len()))
+        });
+        self.matches = scored.into_iter().map(|(_, index)| index).collect();
+        self.selected = 0;
+    }
+
+    pub fn matches(&self) -> impl Iterator<Item = &str> {
+        self.matches
+            .iter()
+            .map(move |index| self.files[*index].as_str())
+    }
+
+    pub fn match_count(&self) -> usize {
+        self.matches.len()
+    }
+
+    pub fn file_count(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn selection(&self) -> Option<&str> {
+        self.matches
+            .get(self.selected)
+            .map(|index| self.files[*index].as_str())
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected.saturating_add(1) < self.matches.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+}
+
+struct IgnoreRule {
+    //directory of the .gitignore the rule came from, relative to the walk root
+    base: String,
+    pattern: String,
+    negated: bool,
+    directory_only: bool,
+    //patterns with a slash in them match the whole path, others just the name
+    anchored: bool,
+}
+
+impl IgnoreRule {
+    fn parse(base: &str, line: &str) -> Option<Self> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let (directory_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let anchored = line.contains('/');
+        Some(Self {
+            base: base.to_string(),
+            pattern: line.trim_start_matches('/').to_string(),
+            negated,
+            directory_only,
+            anchored,
+        })
+    }
+
+    fn matches(&self, path: &str, name: &str, is_dir: bool) -> bool {
+        if self.directory_only && !is_dir {
+            return false;
+        }
+        if self.anchored {
+            match path.strip_prefix(&self.base) {
+                Some(relative) => glob_match(&self.pattern, relative),
+                None => false,
+            }
+        } else {
+            glob_match(&self.pattern, name)
+        }
+    }
+}
+
+fn is_ignored(rules: &[IgnoreRule], path: &str, name: &str, is_dir: bool) -> bool {
+    //the last matching rule decides, so a later `!pattern` can re-include a file
+    rules
+        .iter()
+        .rev()
+        .find(|rule| rule.matches(path, name, is_dir))
+        .is_some_and(|rule| !rule.negated)
+}
+
+//.gitignore files above `root`, up to the top of the repository, still apply to what is below it
+fn ancestor_rules(root: &Path) -> Vec<IgnoreRule> {
+    let mut rules = Vec::new();
+    let root = match fs::canonicalize(root) {
+        Ok(root) => root,
+        Err(_) => return rules,
+    };
+    if root.join(".git").exists() {
+        return rules;
+    }
+    for ancestor in root.ancestors().skip(1) {
+        //anchored patterns of an ancestor are relative to it, so only those leading into `root` are kept
+        let relative = match root.strip_prefix(ancestor) {
+            Ok(relative) => format!("{}/", relative.to_string_lossy()),
+            Err(_) => break,
+        };
+        if let Ok(contents) = fs::read_to_string(ancestor.join(".gitignore")) {
+            let mut found: Vec<IgnoreRule> = contents
+                .lines()
+                .filter_map(|line| IgnoreRule::parse("", line))
+                .filter_map(|mut rule| {
+                    if rule.anchored {
+                        rule.pattern = rule.pattern.strip_prefix(&relative)?.to_string();
+                    }
+                    Some(rule)
+                })
+                .collect();
+            //rules from further up have lower priority
+            found.append(&mut rules);
+            rules = found;
+        }
+        if ancestor.join(".git").exists() {
+            break;
+        }
+    }
+    rules
+}
+
+fn walk(dir: &Path, prefix: &str, rules: &mut Vec<IgnoreRule>, files: &mut Vec<String>) {
+    let rule_count = rules.len();
+    if let Ok(contents) = fs::read_to_string(dir.join(".gitignore")) {
+        rules.extend(
+            contents
+                .lines()
+                .filter_map(|line| IgnoreRule::parse(prefix, line)),
+        );
+    }
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    let mut entries: Vec<_> = entries.filter_map(Result::ok).collect();
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        if files.len() >= MAX_FILES {
+            break;
+        }
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name == ".git" {
+            continue;
+        }
+        let path = format!("{}{}", prefix, name);
+        let is_dir = entry.file_type().map(|kind| kind.is_dir()).unwrap_or(false);
+        if is_ignored(rules, &path, &name, is_dir) {
+            continue;
+        }
+        if is_dir {
+            walk(&entry.path(), &format!("{}/", path), rules, files);
+        } else {
+            files.push(path);
+        }
+    }
+    rules.truncate(rule_count);
+}
+
+//shell style glob: `*` and `?` stay within a path segment, `**` crosses them
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    glob_match_from(&pattern, &text)
+}
+
+fn glob_match_from(pattern: &[char], text: &[char]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some('*') if pattern.get(1) == Some(&'*') => {
+            let mut rest = &pattern[2..];
+            if rest.first() == Some(&'/') {
+                //`**/` may also match no directories at all
+                if glob_match_from(&rest[1..], text) {
+                    return true;
+                }
+                rest = &rest[1..];
+                return (0..text.len())
+                    .filter(|i| text[*i] == '/')
+                    .any(|i| glob_match_from(rest, &text[i + 1..]));
+            }
+            (0..=text.len()).any(|i| glob_match_from(rest, &text[i..]))
+        }
+        Some('*') => {
+            let rest = &pattern[1..];
+            for i in 0..=text.len() {
+                if glob_match_from(rest, &text[i..]) {
+                    return true;
+                }
+                if text.get(i) == Some(&'/') {
+                    break;
+                }
+            }
+            false
+        }
+        Some('?') => {
+            !text.is_empty() && text[0] != '/' && glob_match_from(&pattern[1..], &text[1..])
+        }
+        Some('[') => match pattern.iter().position(|c| *c == ']') {
+            Some(close) if close > 1 && !text.is_empty() => {
+                let class = &pattern[1..close];
+                let (negated, class) = match class.first() {
+                    Some('!') | Some('^') => (true, &class[1..]),
+                    _ => (false, class),
+                };
+                let mut found = false;
+                let mut i = 0;
+                while i < class.len() {
+                    if i + 2 < class.len() && class[i + 1] == '-' {
+                        found |= class[i] <= text[0] && text[0] <= class[i + 2];
+                        i += 3;
+                    } else {
+                        found |= class[i] == text[0];
+                        i += 1;
+                    }
+                }
+                found != negated && glob_match_from(&pattern[close + 1..], &text[1..])
+            }
+            _ => !text.is_empty() && text[0] == '[' && glob_match_from(&pattern[1..], &text[1..]),
+        },
+        Some(c) => !text.is_empty() && text[0] == *c && glob_match_from(&pattern[1..], &text[1..]),
+    }
+}
+
+//subsequence match of `query` in `candidate`, higher is better, None if not all query characters are found.
+//matches at the start of words and runs of consecutive characters are favoured, as are matches in the file name.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i64> {
+    let candidate: Vec<char> = candidate.chars().collect();
+    let name_start = candidate
+        .iter()
+        .rposition(|c| *c == '/')
+        .map_or(0, |i| i + 1);
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous: Option<usize> = None;
+    for q in query.chars().filter(|c| !c.is_whitespace()) {
+        let q = q.to_lowercase().next().unwrap_or(q);
+        let found = candidate[position..]
+            .iter()
+            .position(|c| c.to_lowercase().next() == Some(q))?
+            + position;
+        score += 1;
+        let boundary = found == 0 || matches!(candidate[found - 1], '/' | '_' | '-' | '.' | ' ');
+        if boundary {
+            score += 8;
+        }
+        if found >= name_start {
+            score += 4;
+        }
+        match previous {
+            Some(previous) if previous + 1 == found => score += 5,
+            Some(previous) => score -= (found - previous - 1).min(5) as i64,
+            None => {}
+        }
+        previous = Some(found);
+        position = found + 1;
+    }
+    Some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fuzzy_prefers_file_names_and_word_starts() {
+        assert_eq!(fuzzy_score("xyz", "src/editor.rs"), None);
+        let editor = fuzzy_score("edr", "src/editor.rs").unwrap();
+        let nested = fuzzy_score("edr", "examples/demo/reader.rs").unwrap();
+        assert!(editor > nested);
+        let exact = fuzzy_score("row", "src/row.rs").unwrap();
+        let scattered = fuzzy_score("row", "src/terminal/overwrite.rs").unwrap();
+        assert!(exact > scattered);
+    }
+
+    #[test]
+    fn gitignore_patterns() {
+        assert!(glob_match("*.rs", "main.rs"));
+        assert!(!glob_match("*.rs", "src/main.rs"));
+        assert!(glob_match("src/**/*.rs", "src/a/b/main.rs"));
+        assert!(glob_match("**/target", "target"));
+        assert!(glob_match("file[0-9].txt", "file7.txt"));
+        assert!(!glob_match("file[!0-9].txt", "file7.txt"));
+
+        let rules: Vec<IgnoreRule> = ["target/", "*.log", "!keep.log", "/build"]
+            .iter()
+            .filter_map(|line| IgnoreRule::parse("", line))
+            .collect();
+        assert!(is_ignored(&rules, "target", "target", true));
+        assert!(!is_ignored(&rules, "target", "target", false));
+        assert!(is_ignored(&rules, "logs/debug.log", "debug.log", false));
+        assert!(!is_ignored(&rules, "logs/keep.log", "keep.log", false));
+        assert!(is_ignored(&rules, "build", "build", true));
+        assert!(!is_ignored(&rules, "src/build", "build", true));
+    }
+}
@@ -0,0 +1,344 @@
+use std::ops::Range;
+use std::path::Path;
+use std::process::Command;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+
+//past this many differing lines we stop looking for the shortest edit script
+//and mark the whole changed region as a single hunk
+const MAX_EDIT_DISTANCE: usize = 2000;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LineChange {
+    Added,
+    Modified,
+    Deleted,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Hunk {
+    //lines in the index version
+    pub old: Range<usize>,
+    //lines in the buffer
+    pub new: Range<usize>,
+}
+
+impl Hunk {
+    pub fn change(&self) -> LineChange {
+        if self.old.is_empty() {
+            LineChange::Added
+        } else if self.new.is_empty() {
+            LineChange::Deleted
+        } else {
+            LineChange::Modified
+        }
+    }
+
+    //a deletion has no lines in the buffer, so it is shown on the line that now sits where the removed lines used to be
+    pub fn contains(&self, line: usize) -> bool {
+        if self.new.is_empty() {
+            line == self.new.start
+        } else {
+            self.new.contains(&line)
+        }
+    }
+}
+
+pub struct GitDiff {
+    base: Arc<Vec<String>>,
+    hunks: Vec<Hunk>,
+    revision: Option<usize>,
+    //a diff running in the background and the revision it is for
+    pending: Option<(usize, Receiver<Vec<Hunk>>)>,
+}
+
+impl GitDiff {
+    //reads the version of the file staged in the git index, None if the file is not tracked
+    pub fn load(file_name: &str) -> Option<Self> {
+        let path = Path::new(file_name);
+        let dir = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let name = path.file_name()?.to_str()?;
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .arg("show")
+            .arg(format!(":./{}", name))
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let contents = String::from_utf8_lossy(&output.stdout);
+        Some(Self {
+            base: Arc::new(contents.lines().map(String::from).collect()),
+            hunks: Vec::new(),
+            revision: None,
+            pending: None,
+        })
+    }
+
+    //starts recomputing the hunks in the background unless they are for this revision already, so
+    //a big diff does not hold up typing. Until it is done the hunks of an earlier revision are kept.
+    pub fn update(&mut self, lines: &[&str], revision: usize) {
+        self.collect();
+        //one diff at a time, the next update picks up whatever changed meanwhile
+        if self.revision == Some(revision) || self.pending.is_some() {
+            return;
+        }
+        let base = Arc::clone(&self.base);
+        let lines: Vec<String> = lines.iter().map(|line| (*line).to_string()).collect();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let base: Vec<&str> = base.iter().map(String::as_str).collect();
+            let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+            let _ = sender.send(diff(&base, &lines));
+        });
+        self.pending = Some((revision, receiver));
+    }
+
+    //recomputes the hunks right away, for when they have to match the document
+    pub fn update_now(&mut self, lines: &[&str], revision: usize) {
+        self.collect();
+        if self.revision == Some(revision) {
+            return;
+        }
+        let base: Vec<&str> = self.base.iter().map(String::as_str).collect();
+        self.hunks = diff(&base, lines);
+        self.revision = Some(revision);
+    }
+
+    //takes the hunks of a finished background diff, unless newer ones were computed meanwhile
+    fn collect(&mut self) {
+        let hunks = match &self.pending {
+            Some((_, receiver)) => match receiver.try_recv() {
+                Ok(hunks) => Some(hunks),
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => None,
+            },
+            None => return,
+        };
+        let revision = match self.pending.take() {
+            Some((revision, _)) => revision,
+            None => return,
+        };
+        if let Some(hunks) = hunks {
+            if self.revision.is_none_or(|current| current < revision) {
+                self.hunks = hunks;
+                self.revision = Some(revision);
+            }
+        }
+    }
+
+    pub fn change_at(&self, line: usize) -> Option<LineChange> {
+        self.hunk_at(line).map(Hunk::change)
+    }
+
+    pub fn hunk_at(&self, line: usize) -> Option<&Hunk> {
+        self.hunks.iter().find(|hunk| hunk.contains(line))
+    }
+
+    pub fn next_hunk(&self, line: usize) -> Option<&Hunk> {
+        self.hunks.iter().find(|hunk| hunk.new.start > line)
+    }
+
+    pub fn previous_hunk(&self, line: usize) -> Option<&Hunk> {
+        self.hunks.iter().rev().find(|hunk| hunk.new.start < line)
+    }
+
+    pub fn base_lines(&self, hunk: &Hunk) -> &[String] {
+        &self.base[hunk.old.clone()]
+    }
+}
+
+//Myers' diff over lines, returning the regions where `old` and `new` differ
+pub fn diff(old: &[&str], new: &[&str]) -> Vec<Hunk> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+    if a.is_empty() && b.is_empty() {
+        return Vec::new();
+    }
+
+    let matches = shortest_edit(a, b).unwrap_or_default();
+
+    //everything between two consecutive matching lines is one hunk
+    let mut hunks = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    for (x, y) in matches
+        .into_iter()
+        .chain(std::iter::once((a.len(), b.len())))
+    {
+        if x > i || y > j {
+            hunks.push(Hunk {
+                old: prefix + i..prefix + x,
+                new: prefix + j..prefix + y,
+            });
+        }
+        i = x + 1;
+        j = y + 1;
+    }
+    hunks
+}
+
+//returns the pairs of matching line indexes along the shortest edit script,
+//or None when the two sides are too far apart to be worth it
+#[allow(clippy::cast_possible_wrap, clippy::cast_sign_loss)]
+fn shortest_edit(a: &[&str], b: &[&str]) -> Option<Vec<(usize, usize)>> {
+    let n = a.len() as isize;
+    let m = b.len() as isize;
+    let max = a.len() + b.len();
+    let offset = max as isize;
+    let mut v = vec![0isize; 2 * max + 2];
+    let mut trace = Vec::new();
+
+    'search: for d in 0..=max as isize {
+        if d as usize > MAX_EDIT_DISTANCE {
+            return None;
+        }
+        //only diagonals -d..=d are looked at when walking back from step d
+        trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let index = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[index - 1] < v[index + 1]) {
+                v[index + 1]
+            } else {
+                v[index - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[index] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    //walk back through the trace to recover the diagonals (matching lines)
+    let mut matches = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let at = |k: isize| v[(k + d) as usize];
+        let k = x - y;
+        let previous_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let previous_x = if d > 0 { at(previous_k) } else { 0 };
+        let previous_y = previous_x - previous_k;
+        while x > previous_x && y > previous_y {
+            x -= 1;
+            y -= 1;
+            matches.push((x as usize, y as usize));
+        }
+        if d > 0 {
+            x = previous_x;
+            y = previous_y;
+        }
+    }
+    matches.reverse();
+    Some(matches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identical_files_have_no_hunks() {
+        assert!(diff(&["a", "b"], &["a", "b"]).is_empty());
+    }
+
+    #[test]
+    fn classifies_added_modified_and_deleted_lines() {
+        let old = ["fn main() {", "    a();", "    b();", "    c();", "}"];
+        let new = ["// entry", "fn main() {", "    a();", "    B();", "}"];
+        let hunks = diff(&old, &new);
+        assert_eq!(
+            hunks,
+            vec![
+                Hunk {
+                    old: 0..0,
+                    new: 0..1
+                },
+                Hunk {
+                    old: 2..4,
+                    new: 3..4
+                },
+            ]
+        );
+        assert_eq!(hunks[0].change(), LineChange::Added);
+        assert_eq!(hunks[1].change(), LineChange::Modified);
+
+        let hunks = diff(&old, &["fn main() {", "}"]);
+        assert_eq!(
+            hunks,
+            vec![Hunk {
+                old: 1..4,
+                new: 1..1
+            }]
+        );
+        assert_eq!(hunks[0].change(), LineChange::Deleted);
+        assert!(hunks[0].contains(1));
+    }
+
+    #[test]
+    fn diffs_in_the_background() {
+        let mut git = GitDiff {
+            base: Arc::new(vec!["a".to_string(), "b".to_string(), "c".to_string()]),
+            hunks: Vec::new(),
+            revision: None,
+            pending: None,
+        };
+        git.update(&["a", "x", "c"], 1);
+        //the diff for revision 1 is already running, revision 2 has to wait for it
+        git.update(&["a", "x", "c", "d"], 2);
+        while git.pending.is_some() {
+            thread::yield_now();
+            git.collect();
+        }
+        assert_eq!(git.revision, Some(1));
+        assert_eq!(git.change_at(1), Some(LineChange::Modified));
+
+        git.update_now(&["a", "b", "c", "d"], 3);
+        assert_eq!(git.revision, Some(3));
+        assert_eq!(git.change_at(1), None);
+        assert_eq!(git.change_at(3), Some(LineChange::Added));
+    }
+
+    #[test]
+    fn finds_the_shortest_edit_between_distant_sides() {
+        let old: Vec<String> = (0..500).map(|i| format!("line {}", i)).collect();
+        //every third line changed
+        let new: Vec<String> = (0..500)
+            .map(|i| {
+                if i % 3 == 1 {
+                    format!("new {}", i)
+                } else {
+                    format!("line {}", i)
+                }
+            })
+            .collect();
+        let old: Vec<&str> = old.iter().map(String::as_str).collect();
+        let new: Vec<&str> = new.iter().map(String::as_str).collect();
+        let hunks = diff(&old, &new);
+        assert_eq!(hunks.len(), 167);
+        assert!(hunks
+            .iter()
+            .all(|hunk| hunk.old.len() == 1 && hunk.new == hunk.old));
+    }
+}
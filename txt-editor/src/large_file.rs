@@ -0,0 +1,210 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Error, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+//the index remembers where every STRIDE-th line starts, the lines in between are found by reading forward
+const STRIDE: usize = 1024;
+//anything longer is cut off when displayed, so one enormous line cannot eat all the memory
+const MAX_LINE_LENGTH: usize = 64 * 1024;
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+#[derive(Default)]
+struct LineIndex {
+    //byte offset of line 0, STRIDE, 2 * STRIDE...
+    checkpoints: Vec<u64>,
+    lines: usize,
+    complete: bool,
+}
+
+//a file too big to hold in memory, read a window of lines at a time
+pub struct LargeFile {
+    file: File,
+    index: Arc<Mutex<LineIndex>>,
+}
+
+impl LargeFile {
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        let file = File::open(path)?;
+        let index = Arc::new(Mutex::new(LineIndex {
+            checkpoints: vec![0],
+            ..LineIndex::default()
+        }));
+        let indexer = Arc::clone(&index);
+        let indexed = File::open(path)?;
+        thread::spawn(move || build_index(indexed, &indexer));
+        Ok(Self { file, index })
+    }
+
+    //lines found so far and whether the whole file has been indexed
+    pub fn line_count(&self) -> (usize, bool) {
+        let index = lock(&self.index);
+        (index.lines, index.complete)
+    }
+
+    fn checkpoint(&self, line: usize) -> (usize, u64) {
+        let index = lock(&self.index);
+        let checkpoint = (line / STRIDE).min(index.checkpoints.len() - 1);
+        (checkpoint * STRIDE, index.checkpoints[checkpoint])
+    }
+
+    //a reader positioned at the start of `line`
+    fn reader_at(&self, line: usize) -> Result<BufReader<&File>, Error> {
+        let (mut current, offset) = self.checkpoint(line);
+        (&self.file).seek(SeekFrom::Start(offset))?;
+        let mut reader = BufReader::with_capacity(CHUNK_SIZE, &self.file);
+        let mut buffer = Vec::new();
+        while current < line && read_line(&mut reader, &mut buffer)? {
+            current += 1;
+        }
+        Ok(reader)
+    }
+
+    pub fn read_lines(&self, start: usize, count: usize) -> Result<Vec<String>, Error> {
+        let mut reader = self.reader_at(start)?;
+        let mut lines = Vec::with_capacity(count);
+        let mut buffer = Vec::new();
+        while lines.len() < count && read_line(&mut reader, &mut buffer)? {
+            lines.push(String::from_utf8_lossy(&buffer).to_string());
+        }
+        Ok(lines)
+    }
+
+    //first line at or after `start` containing `query`, scanning the file a line at a time
+    pub fn find_forward(&self, query: &str, start: usize) -> Result<Option<usize>, Error> {
+        let mut reader = self.reader_at(start)?;
+        let mut buffer = Vec::new();
+        let mut line = start;
+        while read_line(&mut reader, &mut buffer)? {
+            if String::from_utf8_lossy(&buffer).contains(query) {
+                return Ok(Some(line));
+            }
+            line += 1;
+        }
+        Ok(None)
+    }
+
+    //last line before `end` containing `query`, checking one indexed stretch of lines at a time going back
+    pub fn find_backward(&self, query: &str, end: usize) -> Result<Option<usize>, Error> {
+        let mut block_end = end;
+        while block_end > 0 {
+            let (block_start, _) = self.checkpoint(block_end - 1);
+            let mut reader = self.reader_at(block_start)?;
+            let mut buffer = Vec::new();
+            let mut found = None;
+            for line in block_start..block_end {
+                if !read_line(&mut reader, &mut buffer)? {
+                    break;
+                }
+                if String::from_utf8_lossy(&buffer).contains(query) {
+                    found = Some(line);
+                }
+            }
+            if found.is_some() {
+                return Ok(found);
+            }
+            block_end = block_start;
+        }
+        Ok(None)
+    }
+}
+
+fn lock(index: &Mutex<LineIndex>) -> std::sync::MutexGuard<'_, LineIndex> {
+    //the indexer never panics while holding the lock, but if it did the data is still usable
+    index
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+fn build_index(file: File, index: &Mutex<LineIndex>) {
+    let mut reader = BufReader::with_capacity(CHUNK_SIZE, file);
+    let mut offset: u64 = 0;
+    let mut lines = 0;
+    let mut checkpoints = Vec::new();
+    //whether the last byte seen ended a line, a file not ending in a newline still has a last line
+    let mut at_line_start = true;
+    loop {
+        let chunk = match reader.fill_buf() {
+            Ok([]) | Err(_) => break,
+            Ok(chunk) => chunk,
+        };
+        for (i, byte) in chunk.iter().enumerate() {
+            if *byte == b'\n' {
+                lines += 1;
+                if lines % STRIDE == 0 {
+                    checkpoints.push(offset + i as u64 + 1);
+                }
+            }
+        }
+        at_line_start = chunk.last() == Some(&b'\n');
+        let len = chunk.len();
+        reader.consume(len);
+        offset += len as u64;
+
+        let mut index = lock(index);
+        index.checkpoints.append(&mut checkpoints);
+        index.lines = lines;
+    }
+    let mut index = lock(index);
+    if !at_line_start {
+        index.lines = lines + 1;
+    }
+    index.complete = true;
+}
+
+//reads one line without its line ending into `buffer`, keeping at most MAX_LINE_LENGTH bytes of it.
+//Returns false at the end of the file.
+fn read_line<R: BufRead>(reader: &mut R, buffer: &mut Vec<u8>) -> Result<bool, Error> {
+    buffer.clear();
+    let mut read_any = false;
+    loop {
+        let chunk = reader.fill_buf()?;
+        if chunk.is_empty() {
+            break;
+        }
+        read_any = true;
+        let (line, used, done) = match chunk.iter().position(|b| *b == b'\n') {
+            Some(end) => (&chunk[..end], end + 1, true),
+            None => (chunk, chunk.len(), false),
+        };
+        let room = MAX_LINE_LENGTH.saturating_sub(buffer.len());
+        buffer.extend_from_slice(&line[..line.len().min(room)]);
+        reader.consume(used);
+        if done {
+            break;
+        }
+    }
+    if buffer.last() == Some(&b'\r') {
+        buffer.pop();
+    }
+    Ok(read_any)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn reads_windows_and_searches_past_checkpoints() {
+        let path = std::env::temp_dir().join(format!("large-file-{}.txt", std::process::id()));
+        let contents: String = (0..3000).map(|i| format!("line {}\r\n", i)).collect();
+        fs::write(&path, format!("{}last", contents)).unwrap();
+
+        let file = LargeFile::open(&path).unwrap();
+        while !file.line_count().1 {
+            thread::yield_now();
+        }
+        assert_eq!(file.line_count(), (3001, true));
+        assert_eq!(
+            file.read_lines(2047, 3).unwrap(),
+            vec!["line 2047", "line 2048", "line 2049"]
+        );
+        assert_eq!(file.read_lines(2999, 5).unwrap(), vec!["line 2999", "last"]);
+        assert_eq!(file.find_forward("line 2500", 10).unwrap(), Some(2500));
+        assert_eq!(file.find_backward("line 10", 2500).unwrap(), Some(1099));
+        assert_eq!(file.find_backward("nowhere", 2500).unwrap(), None);
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,308 @@
+use crate::storage;
+use std::collections::{BTreeMap, VecDeque};
+use std::io::Error;
+use termion::event::Key;
+
+const MACROS_FILE: &str = "macros";
+//keys one replay may feed the editor, counting those of the macros it replays in turn. A macro
+//replaying itself never runs out of keys, this is what stops it.
+const MAX_REPLAYED_KEYS: usize = 100_000;
+
+#[derive(Default)]
+pub struct Macros {
+    registers: BTreeMap<char, Vec<Key>>,
+    recording: Option<(char, Vec<Key>)>,
+}
+
+impl Macros {
+    //macros recorded in earlier sessions, one register per line as `a:keys`
+    pub fn load() -> Self {
+        let mut macros = Self::default();
+        if let Some(contents) = storage::read(MACROS_FILE) {
+            for line in contents.lines() {
+                let mut chars = line.chars();
+                if let (Some(register), Some(':')) = (chars.next(), chars.next()) {
+                    if let Some(keys) = decode_keys(chars.as_str()) {
+                        macros.registers.insert(register, keys);
+                    }
+                }
+            }
+        }
+        macros
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let mut contents = String::new();
+        for (register, keys) in &self.registers {
+            contents.push_str(&format!("{}:{}\n", register, encode_keys(keys)));
+        }
+        storage::write(MACROS_FILE, &contents)
+    }
+
+    pub fn start_recording(&mut self, register: char) {
+        self.recording = Some((register, Vec::new()));
+    }
+
+    //stores what was recorded, minus the key that stopped the recording
+    pub fn stop_recording(&mut self) -> Option<(char, usize)> {
+        let (register, mut keys) = self.recording.take()?;
+        keys.pop();
+        let len = keys.len();
+        self.registers.insert(register, keys);
+        Some((register, len))
+    }
+
+    pub fn recording(&self) -> Option<char> {
+        self.recording.as_ref().map(|(register, _)| *register)
+    }
+
+    pub fn record(&mut self, key: Key) {
+        if let Some((_, keys)) = &mut self.recording {
+            keys.push(key);
+        }
+    }
+
+    pub fn get(&self, register: char) -> Option<&[Key]> {
+        self.registers.get(&register).map(Vec::as_slice)
+    }
+}
+
+//keys queued by macro replays, consumed before the terminal is read
+#[derive(Default)]
+pub struct Replay {
+    pending: VecDeque<Key>,
+    //handed out since the queue was last empty
+    replayed: usize,
+    stopped: bool,
+}
+
+impl Replay {
+    //queues `keys` `count` times in front of whatever is still pending, so a macro can replay
+    //another one. Past what a replay may take nothing is queued and the replay is stopped.
+    pub fn queue(&mut self, keys: &[Key], count: usize) {
+        let total = keys
+            .len()
+            .saturating_mul(count)
+            .saturating_add(self.pending.len())
+            .saturating_add(self.replayed);
+        if total > MAX_REPLAYED_KEYS {
+            self.stop();
+            return;
+        }
+        for _ in 0..count {
+            for key in keys.iter().rev() {
+                self.pending.push_front(*key);
+            }
+        }
+    }
+
+    //None once the queue is empty, or the replay has taken too many keys and is dropped
+    pub fn next_key(&mut self) -> Option<Key> {
+        if self.replayed >= MAX_REPLAYED_KEYS {
+            self.stop();
+        }
+        match self.pending.pop_front() {
+            Some(key) => {
+                self.replayed += 1;
+                Some(key)
+            }
+            None => {
+                self.replayed = 0;
+                None
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    //whether a replay was dropped since the last call
+    pub fn take_stopped(&mut self) -> bool {
+        std::mem::take(&mut self.stopped)
+    }
+
+    fn stop(&mut self) {
+        self.pending.clear();
+        self.replayed = 0;
+        self.stopped = true;
+    }
+}
+
+//vim style key notation: plain characters as themselves, everything else as <Name>
+fn encode_key(key: Key) -> String {
+    match key {
+        Key::Char('<') => "<lt>".to_string(),
+        Key::Char('\n') => "<Enter>".to_string(),
+        Key::Char('\t') => "<Tab>".to_string(),
+        Key::Char(c) => c.to_string(),
+        Key::Ctrl(c) => format!("<C-{}>", c),
+        Key::Alt(c) => format!("<A-{}>", c),
+        Key::F(n) => format!("<F{}>", n),
+        Key::Backspace => "<BS>".to_string(),
+        Key::Left => "<Left>".to_string(),
+        Key::ShiftLeft => "<S-Left>".to_string(),
+        Key::AltLeft => "<A-Left>".to_string(),
+        Key::CtrlLeft => "<C-Left>".to_string(),
+        Key::Right => "<Right>".to_string(),
+        Key::ShiftRight => "<S-Right>".to_string(),
+        Key::AltRight => "<A-Right>".to_string(),
+        Key::CtrlRight => "<C-Right>".to_string(),
+        Key::Up => "<Up>".to_string(),
+        Key::ShiftUp => "<S-Up>".to_string(),
+        Key::AltUp => "<A-Up>".to_string(),
+        Key::CtrlUp => "<C-Up>".to_string(),
+        Key::Down => "<Down>".to_string(),
+        Key::ShiftDown => "<S-Down>".to_string(),
+        Key::AltDown => "<A-Down>".to_string(),
+        Key::CtrlDown => "<C-Down>".to_string(),
+        Key::Home => "<Home>".to_string(),
+        Key::CtrlHome => "<C-Home>".to_string(),
+        Key::End => "<End>".to_string(),
+        Key::CtrlEnd => "<C-End>".to_string(),
+        Key::PageUp => "<PageUp>".to_string(),
+        Key::PageDown => "<PageDown>".to_string(),
+        Key::BackTab => "<S-Tab>".to_string(),
+        Key::Delete => "<Del>".to_string(),
+        Key::Insert => "<Insert>".to_string(),
+        Key::Esc => "<Esc>".to_string(),
+        _ => "<Null>".to_string(),
+    }
+}
+
+fn decode_key(name: &str) -> Option<Key> {
+    let key = match name {
+        "lt" => Key::Char('<'),
+        "Enter" => Key::Char('\n'),
+        "Tab" => Key::Char('\t'),
+        "BS" => Key::Backspace,
+        "Left" => Key::Left,
+        "S-Left" => Key::ShiftLeft,
+        "A-Left" => Key::AltLeft,
+        "C-Left" => Key::CtrlLeft,
+        "Right" => Key::Right,
+        "S-Right" => Key::ShiftRight,
+        "A-Right" => Key::AltRight,
+        "C-Right" => Key::CtrlRight,
+        "Up" => Key::Up,
+        "S-Up" => Key::ShiftUp,
+        "A-Up" => Key::AltUp,
+        "C-Up" => Key::CtrlUp,
+        "Down" => Key::Down,
+        "S-Down" => Key::ShiftDown,
+        "A-Down" => Key::AltDown,
+        "C-Down" => Key::CtrlDown,
+        "Home" => Key::Home,
+        "C-Home" => Key::CtrlHome,
+        "End" => Key::End,
+        "C-End" => Key::CtrlEnd,
+        "PageUp" => Key::PageUp,
+        "PageDown" => Key::PageDown,
+        "S-Tab" => Key::BackTab,
+        "Del" => Key::Delete,
+        "Insert" => Key::Insert,
+        "Esc" => Key::Esc,
+        "Null" => Key::Null,
+        _ => {
+            let single = |rest: &str| {
+                let mut chars = rest.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Some(c),
+                    _ => None,
+                }
+            };
+            if let Some(rest) = name.strip_prefix("C-") {
+                Key::Ctrl(single(rest)?)
+            } else if let Some(rest) = name.strip_prefix("A-") {
+                Key::Alt(single(rest)?)
+            } else if let Some(rest) = name.strip_prefix('F') {
+                Key::F(rest.parse().ok()?)
+            } else {
+                return None;
+            }
+        }
+    };
+    Some(key)
+}
+
+pub fn encode_keys(keys: &[Key]) -> String {
+    keys.iter().map(|key| encode_key(*key)).collect()
+}
+
+pub fn decode_keys(text: &str) -> Option<Vec<Key>> {
+    let mut keys = Vec::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '<' {
+            let rest = chars.as_str();
+            //in `<A->>` the first `>` is the key itself, not the closing bracket
+            let from = if rest.starts_with("C->") || rest.starts_with("A->") {
+                3
+            } else {
+                0
+            };
+            let end = rest[from..].find('>')? + from;
+            keys.push(decode_key(&rest[..end])?);
+            chars = rest[end + 1..].chars();
+        } else {
+            keys.push(Key::Char(c));
+        }
+    }
+    Some(keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_round_trip_through_notation() {
+        let keys = vec![
+            Key::Char('a'),
+            Key::Char('<'),
+            Key::Char(' '),
+            Key::Char('\n'),
+            Key::Ctrl('s'),
+            Key::Alt('>'),
+            Key::ShiftUp,
+            Key::F(5),
+            Key::Esc,
+        ];
+        let text = encode_keys(&keys);
+        assert_eq!(text, "a<lt> <Enter><C-s><A->><S-Up><F5><Esc>");
+        assert_eq!(decode_keys(&text), Some(keys));
+        assert_eq!(decode_keys("<Nope>"), None);
+    }
+
+    #[test]
+    fn a_macro_replaying_itself_is_stopped() {
+        //register a replays register a, the way the editor reads Ctrl-E, the register and Enter
+        let keys = decode_keys("<C-e>a<Enter>").unwrap();
+        let mut replay = Replay::default();
+        replay.queue(&keys, 1);
+        let mut handed_out = 0;
+        while let Some(key) = replay.next_key() {
+            handed_out += 1;
+            if key == Key::Ctrl('e') {
+                let register = replay.next_key();
+                let enter = replay.next_key();
+                handed_out += 2;
+                if (register, enter) == (Some(Key::Char('a')), Some(Key::Char('\n'))) {
+                    replay.queue(&keys, 1);
+                }
+            }
+        }
+        assert!(handed_out <= MAX_REPLAYED_KEYS + 2);
+        assert!(replay.is_empty());
+        assert!(replay.take_stopped());
+        assert!(!replay.take_stopped());
+
+        //a later replay starts with a fresh allowance
+        replay.queue(&keys, 2);
+        assert_eq!(replay.next_key(), Some(Key::Ctrl('e')));
+        assert!(!replay.take_stopped());
+        replay.queue(&keys, MAX_REPLAYED_KEYS);
+        assert!(replay.is_empty());
+        assert!(replay.take_stopped());
+    }
+}
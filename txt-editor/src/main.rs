@@ -2,6 +2,21 @@ mod editor;
 mod terminal;
 mod document;
 mod row;
+mod git;
+mod encoding;
+mod large_file;
+mod finder;
+mod macros;
+mod storage;
+mod config;
+mod filetype;
+mod filter;
+mod completion;
+mod session;
+mod editorconfig;
+mod status_line;
+mod bookmarks;
+mod spell;
 pub use terminal::Terminal;
 pub use editor::Position;
 pub use document::Document;
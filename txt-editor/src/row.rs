@@ -1,7 +1,10 @@
 use std::cmp;
+use std::ops::Range;
+use termion::style;
 use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct Row {
     string: String,
     len: usize,
@@ -20,20 +23,44 @@ impl From<&str> for Row {
 
 impl Row {
     pub fn render(&self, start: usize, end: usize) -> String {
+        self.render_underlined(start, end, &[])
+    }
+
+    //like render, with the graphemes in `underlined` (misspelled words) underlined
+    pub fn render_underlined(
+        &self,
+        start: usize,
+        end: usize,
+        underlined: &[Range<usize>],
+    ) -> String {
         let end = cmp::min(end, self.string.len());
         let start = cmp::min(start, end);
         let mut result = String::new();
-        for grapheme in self.string[..]
+        let mut underlining = false;
+        for (index, grapheme) in self.string[..]
             .graphemes(true)
+            .enumerate()
             .skip(start)
             .take(end - start)
         {
+            let underline = underlined.iter().any(|range| range.contains(&index));
+            if underline != underlining {
+                if underline {
+                    result.push_str(&format!("{}", style::Underline));
+                } else {
+                    result.push_str(&format!("{}", style::NoUnderline));
+                }
+                underlining = underline;
+            }
             if grapheme == "\t" {
-                result.push_str(" ")
+                result.push(' ')
             } else {
                 result.push_str(grapheme);
             }
         }
+        if underlining {
+            result.push_str(&format!("{}", style::NoUnderline));
+        }
 
         result
     }
@@ -91,4 +118,129 @@ impl Row {
     pub fn as_bytes(&self)->&[u8]{
         self.string.as_bytes()
     }
+
+    //grapheme index of the first match starting at or after `at` going forward, or the last one starting before `at` going backward
+    pub fn find(&self, query: &str, at: usize, forward: bool) -> Option<usize> {
+        if query.is_empty() {
+            return None;
+        }
+        let starts: Vec<usize> = self.string.grapheme_indices(true).map(|(i, _)| i).collect();
+        let byte_at = starts.get(at).copied().unwrap_or(self.string.len());
+        let mut matches = self.string.match_indices(query).map(|(i, _)| i);
+        let found = if forward {
+            matches.find(|i| *i >= byte_at)
+        } else {
+            matches.take_while(|i| *i < byte_at).last()
+        }?;
+        starts.iter().position(|start| *start >= found)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.string
+    }
+
+    //grapheme index where the whitespace at the end of the row starts, None if there is none
+    pub fn trailing_whitespace_start(&self) -> Option<usize> {
+        let trimmed = self.string.trim_end();
+        if trimmed.len() == self.string.len() {
+            None
+        } else {
+            Some(trimmed.graphemes(true).count())
+        }
+    }
+
+    //the whitespace the row starts with
+    pub fn indentation(&self) -> &str {
+        let trimmed = self.string.trim_start();
+        &self.string[..self.string.len() - trimmed.len()]
+    }
+
+    //screen column where grapheme `index` starts, wide characters take two columns
+    pub fn display_column(&self, index: usize) -> usize {
+        self.string.graphemes(true).take(index).map(grapheme_width).sum()
+    }
+
+    pub fn width(&self) -> usize {
+        self.display_column(self.len)
+    }
+
+    //how many graphemes start left of screen column `column`
+    pub fn index_at_column(&self, column: usize) -> usize {
+        let mut start = 0;
+        let mut index = 0;
+        for grapheme in self.string.graphemes(true) {
+            if start >= column {
+                break;
+            }
+            start += grapheme_width(grapheme);
+            index += 1;
+        }
+        index
+    }
+
+    //the graphemes from `start` up to `end`, as they are stored
+    pub fn substring(&self, start: usize, end: usize) -> String {
+        self.string
+            .graphemes(true)
+            .skip(start)
+            .take(end.saturating_sub(start))
+            .collect()
+    }
+
+    pub fn insert_str(&mut self, at: usize, text: &str) {
+        let mut result: String = self.string.graphemes(true).take(at).collect();
+        let remainder: String = self.string.graphemes(true).skip(at).collect();
+        result.push_str(text);
+        result.push_str(&remainder);
+        self.string = result;
+        self.update_len();
+    }
+
+    pub fn delete_range(&mut self, start: usize, end: usize) {
+        let mut result: String = self.string.graphemes(true).take(start).collect();
+        let remainder: String = self.string.graphemes(true).skip(end.max(start)).collect();
+        result.push_str(&remainder);
+        self.string = result;
+        self.update_len();
+    }
+
+    //spaces up to screen column `column`, so text can be put there
+    pub fn pad_to_column(&mut self, column: usize) {
+        let width = self.width();
+        if width < column {
+            self.string.push_str(&" ".repeat(column - width));
+            self.update_len();
+        }
+    }
+}
+
+//tabs are drawn as a single space
+fn grapheme_width(grapheme: &str) -> usize {
+    if grapheme == "\t" {
+        1
+    } else {
+        grapheme.width()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_graphemes_to_display_columns() {
+        let mut row = Row::from("a\t漢字e\u{301}!");
+        assert_eq!(row.len(), 6);
+        assert_eq!(row.display_column(3), 4);
+        assert_eq!(row.width(), 8);
+        assert_eq!(row.index_at_column(3), 3);
+        assert_eq!(row.index_at_column(4), 3);
+        assert_eq!(row.index_at_column(20), 6);
+        assert_eq!(row.substring(2, 4), "漢字");
+        row.delete_range(2, 4);
+        row.insert_str(2, "xy");
+        assert_eq!(row.as_str(), "a\txye\u{301}!");
+        row.pad_to_column(8);
+        assert_eq!(row.as_str(), "a\txye\u{301}!  ");
+    }
 }
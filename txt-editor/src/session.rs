@@ -0,0 +1,222 @@
+use crate::storage;
+use crate::Position;
+use std::io::Error;
+use std::path::{Path, PathBuf};
+
+const SESSION_FILE: &str = "session";
+//older entries are forgotten once there are more than these
+const MAX_FILES: usize = 500;
+const MAX_HISTORY: usize = 100;
+
+//where the cursor and the screen were in a file when it was last open
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct FileState {
+    pub cursor_position: Position,
+    pub offset: Position,
+}
+
+//what the editor remembers between runs, one entry per line as `kind<Tab>fields`.
+//Paths are absolute so the state is found again whatever directory the editor starts in.
+#[derive(Default)]
+pub struct Session {
+    //most recently closed first
+    files: Vec<(PathBuf, FileState)>,
+    //oldest first, like a shell history
+    search_history: Vec<String>,
+    command_history: Vec<String>,
+    //the buffers open on quit, the one on screen first
+    open_files: Vec<PathBuf>,
+}
+
+impl Session {
+    pub fn load() -> Self {
+        storage::read(SESSION_FILE)
+            .map(|contents| Self::parse(&contents))
+            .unwrap_or_default()
+    }
+
+    fn parse(contents: &str) -> Self {
+        let mut session = Self::default();
+        for line in contents.lines() {
+            let (kind, rest) = match line.split_once('\t') {
+                Some(parts) => parts,
+                None => continue,
+            };
+            match kind {
+                "file" => {
+                    let fields: Vec<&str> = rest.splitn(5, '\t').collect();
+                    let numbers: Vec<usize> = fields
+                        .iter()
+                        .take(4)
+                        .filter_map(|field| field.parse().ok())
+                        .collect();
+                    if let (&[x, y, offset_x, offset_y], Some(path)) = (&numbers[..], fields.get(4))
+                    {
+                        let state = FileState {
+                            cursor_position: Position { x, y },
+                            offset: Position {
+                                x: offset_x,
+                                y: offset_y,
+                            },
+                        };
+                        session.files.push((PathBuf::from(path), state));
+                    }
+                }
+                "search" => session.search_history.push(rest.to_string()),
+                "command" => session.command_history.push(rest.to_string()),
+                "open" => session.open_files.push(PathBuf::from(rest)),
+                _ => (),
+            }
+        }
+        session
+    }
+
+    fn serialize(&self) -> String {
+        let mut contents = String::new();
+        for (path, state) in &self.files {
+            contents.push_str(&format!(
+                "file\t{}\t{}\t{}\t{}\t{}\n",
+                state.cursor_position.x,
+                state.cursor_position.y,
+                state.offset.x,
+                state.offset.y,
+                path.display()
+            ));
+        }
+        for query in &self.search_history {
+            contents.push_str(&format!("search\t{}\n", query));
+        }
+        for command in &self.command_history {
+            contents.push_str(&format!("command\t{}\n", command));
+        }
+        for path in &self.open_files {
+            contents.push_str(&format!("open\t{}\n", path.display()));
+        }
+        contents
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        storage::write(SESSION_FILE, &self.serialize())
+    }
+
+    pub fn file_state(&self, path: &Path) -> Option<FileState> {
+        let path = absolute(path);
+        self.files
+            .iter()
+            .find(|(file, _)| *file == path)
+            .map(|(_, state)| *state)
+    }
+
+    pub fn set_file_state(&mut self, path: &Path, state: FileState) {
+        let path = absolute(path);
+        self.files.retain(|(file, _)| *file != path);
+        self.files.insert(0, (path, state));
+        self.files.truncate(MAX_FILES);
+    }
+
+    pub fn open_files(&self) -> &[PathBuf] {
+        &self.open_files
+    }
+
+    pub fn set_open_files(&mut self, paths: &[&Path]) {
+        self.open_files = paths.iter().map(|path| absolute(path)).collect();
+    }
+
+    pub fn search_history(&self) -> &[String] {
+        &self.search_history
+    }
+
+    pub fn add_search(&mut self, query: &str) {
+        add_to_history(&mut self.search_history, query);
+    }
+
+    pub fn command_history(&self) -> &[String] {
+        &self.command_history
+    }
+
+    pub fn add_command(&mut self, command: &str) {
+        add_to_history(&mut self.command_history, command);
+    }
+}
+
+//moves an entry used again to the end instead of keeping duplicates
+fn add_to_history(history: &mut Vec<String>, entry: &str) {
+    if entry.is_empty() {
+        return;
+    }
+    history.retain(|old| old != entry);
+    history.push(entry.to_string());
+    if history.len() > MAX_HISTORY {
+        history.remove(0);
+    }
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    path.canonicalize()
+        .or_else(|_| std::path::absolute(path))
+        .unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_and_moves_entries_to_the_front() {
+        let mut session = Session::default();
+        let state = FileState {
+            cursor_position: Position { x: 3, y: 40 },
+            offset: Position { x: 0, y: 25 },
+        };
+        session.set_file_state(Path::new("/no/such/a b.txt"), state);
+        session.set_file_state(Path::new("/no/such/other"), state);
+        session.set_file_state(Path::new("/no/such/a b.txt"), state);
+        session.add_search("fn main");
+        session.add_search("todo");
+        session.add_search("fn main");
+        session.add_command("sort -u");
+        session.set_open_files(&[Path::new("/no/such/other")]);
+
+        let restored = Session::parse(&session.serialize());
+        assert_eq!(restored.files, session.files);
+        assert_eq!(restored.files[0].0, PathBuf::from("/no/such/a b.txt"));
+        assert_eq!(
+            restored.file_state(Path::new("/no/such/other")),
+            Some(state)
+        );
+        assert_eq!(restored.search_history(), ["todo", "fn main"]);
+        assert_eq!(restored.command_history(), ["sort -u"]);
+        assert_eq!(restored.open_files(), [PathBuf::from("/no/such/other")]);
+        assert!(Session::parse("file\tx\t1\t2\t3\t/a\nnonsense\n")
+            .files
+            .is_empty());
+    }
+
+    #[test]
+    fn forgets_the_oldest_entries_and_finds_files_by_any_path() {
+        let mut session = Session::default();
+        let state = |y| FileState {
+            cursor_position: Position { x: 0, y },
+            offset: Position { x: 0, y: 0 },
+        };
+        for i in 0..MAX_FILES + 5 {
+            session.set_file_state(Path::new(&format!("/no/such/{}", i)), state(i));
+        }
+        assert_eq!(session.files.len(), MAX_FILES);
+        assert_eq!(session.file_state(Path::new("/no/such/4")), None);
+        assert_eq!(session.file_state(Path::new("/no/such/5")), Some(state(5)));
+        for i in 0..MAX_HISTORY + 5 {
+            session.add_search(&format!("query {}", i));
+        }
+        session.add_search("");
+        assert_eq!(session.search_history().len(), MAX_HISTORY);
+        assert_eq!(session.search_history()[0], "query 5");
+
+        //a relative path and the absolute one are the same file
+        let cwd = std::env::current_dir().unwrap();
+        session.set_file_state(Path::new("Cargo.toml"), state(7));
+        assert_eq!(session.file_state(&cwd.join("Cargo.toml")), Some(state(7)));
+        session.set_open_files(&[Path::new("src/../Cargo.toml")]);
+        assert_eq!(session.open_files(), [cwd.join("Cargo.toml")]);
+    }
+}
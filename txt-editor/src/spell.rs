@@ -0,0 +1,389 @@
+use crate::config::Config;
+use crate::filetype::FileType;
+use crate::storage;
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+use std::io::Error;
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+
+const PERSONAL_DICTIONARY_FILE: &str = "dictionary";
+//tried in order when `spell.dictionary` is not set, plain word lists first since they have
+//every form of a word where Hunspell files only have the stems
+const SYSTEM_DICTIONARIES: [&str; 4] = [
+    "/usr/share/dict/american-english",
+    "/usr/share/dict/words",
+    "/usr/share/hunspell/en_US.dic",
+    "/usr/share/myspell/en_US.dic",
+];
+//further than this a suggestion is more likely to be noise than what was meant
+const MAX_DISTANCE: usize = 2;
+pub const MAX_SUGGESTIONS: usize = 5;
+
+//the known words: a system or configured word list plus the personal dictionary
+//in the data directory, which words are added to from the editor
+pub struct SpellChecker {
+    words: HashSet<String>,
+    personal: BTreeSet<String>,
+}
+
+impl SpellChecker {
+    //None without a word list, a personal dictionary alone would flag nearly everything
+    pub fn load(config: &Config) -> Option<Self> {
+        let contents = match config.get("spell.dictionary") {
+            Some(path) => fs::read_to_string(path).ok()?,
+            None => SYSTEM_DICTIONARIES
+                .iter()
+                .find_map(|path| fs::read_to_string(path).ok())?,
+        };
+        let personal = storage::read(PERSONAL_DICTIONARY_FILE).unwrap_or_default();
+        Some(Self::new(&contents, &personal))
+    }
+
+    fn new(word_list: &str, personal: &str) -> Self {
+        let personal: BTreeSet<String> = parse_word_list(personal).collect();
+        let words = parse_word_list(word_list)
+            .chain(personal.iter().cloned())
+            .collect();
+        Self { words, personal }
+    }
+
+    //a capitalized word is fine if the list has it in lower case, not the other way round
+    pub fn is_correct(&self, word: &str) -> bool {
+        self.words.contains(word) || self.words.contains(&word.to_lowercase())
+    }
+
+    pub fn add_word(&mut self, word: &str) -> Result<(), Error> {
+        self.personal.insert(word.to_string());
+        self.words.insert(word.to_string());
+        let mut contents = String::new();
+        for word in &self.personal {
+            contents.push_str(word);
+            contents.push('\n');
+        }
+        storage::write(PERSONAL_DICTIONARY_FILE, &contents)
+    }
+
+    //the closest known words, written with the same capitalization as `word`
+    pub fn suggestions(&self, word: &str) -> Vec<String> {
+        let lower: Vec<char> = word.to_lowercase().chars().collect();
+        let mut candidates: Vec<(usize, String)> = self
+            .words
+            .iter()
+            .filter(|known| known.chars().count().abs_diff(lower.len()) <= MAX_DISTANCE)
+            .filter_map(|known| {
+                let known_lower: Vec<char> = known.to_lowercase().chars().collect();
+                let distance = edit_distance(&lower, &known_lower);
+                (distance <= MAX_DISTANCE).then(|| (distance, match_case(known, word)))
+            })
+            .collect();
+        candidates.sort();
+        let mut suggestions: Vec<String> = Vec::new();
+        for (_, candidate) in candidates {
+            if candidate != word && !suggestions.contains(&candidate) {
+                suggestions.push(candidate);
+            }
+            if suggestions.len() == MAX_SUGGESTIONS {
+                break;
+            }
+        }
+        suggestions
+    }
+
+    //grapheme ranges of the unknown words on a line. Prose is checked as a whole, code only in
+    //line comments and double quoted strings, so identifiers are left alone.
+    pub fn misspellings(&self, line: &str, file_type: &FileType) -> Vec<Range<usize>> {
+        let graphemes: Vec<&str> = line.graphemes(true).collect();
+        let regions = if file_type.is_prose() {
+            std::iter::once(0..graphemes.len()).collect()
+        } else {
+            code_regions(&graphemes, file_type.line_comment())
+        };
+        regions
+            .into_iter()
+            .flat_map(|region| words(&graphemes, region))
+            .filter(|word| !self.is_correct(&graphemes[word.clone()].concat()))
+            .collect()
+    }
+}
+
+//one word per line, Hunspell .dic files starting with a word count and
+//having `/FLAGS` after each word, which add the forms the flags stand for
+fn parse_word_list(contents: &str) -> impl Iterator<Item = String> + '_ {
+    contents
+        .lines()
+        .map(|line| {
+            let (word, flags) = line.split_once('/').unwrap_or((line, ""));
+            (word.trim(), flags.trim())
+        })
+        .filter(|(word, _)| !word.is_empty() && !word.chars().all(|c| c.is_ascii_digit()))
+        .flat_map(|(word, flags)| std::iter::once(word.to_string()).chain(inflections(word, flags)))
+}
+
+//the forms the common suffix flags of the English Hunspell dictionaries give a stem, as their
+//.aff file defines them: `brown/MS` is also "brown's" and "browns". Other flags are ignored.
+fn inflections(stem: &str, flags: &str) -> Vec<String> {
+    flags
+        .chars()
+        .filter_map(|flag| {
+            let suffix = match flag {
+                'S' => return Some(plural(stem)),
+                'M' => return Some(format!("{}'s", stem)),
+                'D' => "ed",
+                'G' => "ing",
+                'J' => "ings",
+                'R' => "er",
+                'Z' => "ers",
+                'T' => "est",
+                'Y' => "ly",
+                'P' => "ness",
+                _ => return None,
+            };
+            Some(with_suffix(stem, suffix))
+        })
+        .collect()
+}
+
+fn plural(stem: &str) -> String {
+    if ["s", "x", "z", "ch", "sh"]
+        .iter()
+        .any(|end| stem.ends_with(end))
+    {
+        format!("{}es", stem)
+    } else if ends_with_consonant_y(stem) {
+        format!("{}ies", &stem[..stem.len() - 1])
+    } else {
+        format!("{}s", stem)
+    }
+}
+
+//a final `e` is dropped before a vowel, a `y` after a consonant becomes `i`: "hoped", "happier"
+fn with_suffix(stem: &str, suffix: &str) -> String {
+    if stem.ends_with('e') && suffix.starts_with(['e', 'i']) {
+        format!("{}{}", &stem[..stem.len() - 1], suffix)
+    } else if ends_with_consonant_y(stem) && !suffix.starts_with('i') {
+        format!("{}i{}", &stem[..stem.len() - 1], suffix)
+    } else {
+        format!("{}{}", stem, suffix)
+    }
+}
+
+fn ends_with_consonant_y(stem: &str) -> bool {
+    let mut chars = stem.chars().rev();
+    chars.next() == Some('y') && chars.next().is_some_and(|c| !"aeiou".contains(c))
+}
+
+//the parts of a code line after a line comment starts or between double quotes
+fn code_regions(graphemes: &[&str], comment: Option<&str>) -> Vec<Range<usize>> {
+    let mut regions = Vec::new();
+    let mut string_start = None;
+    let mut i = 0;
+    while i < graphemes.len() {
+        match (graphemes[i], string_start) {
+            ("\\", Some(_)) => i += 1,
+            ("\"", Some(start)) => {
+                regions.push(start..i);
+                string_start = None;
+            }
+            ("\"", None) => string_start = Some(i + 1),
+            (_, None) if comment.is_some_and(|comment| starts_with(graphemes, i, comment)) => {
+                regions.push(i..graphemes.len());
+                return regions;
+            }
+            _ => (),
+        }
+        i += 1;
+    }
+    regions
+}
+
+fn starts_with(graphemes: &[&str], at: usize, text: &str) -> bool {
+    let len = text.graphemes(true).count();
+    graphemes.len() >= at + len && graphemes[at..at + len].concat() == text
+}
+
+//the words worth checking in `region`: runs of letters with apostrophes inside. Anything glued
+//to digits, underscores or mixed case is taken for an identifier, and whitespace separated
+//chunks that look like addresses or inline code are skipped.
+fn words(graphemes: &[&str], region: Range<usize>) -> Vec<Range<usize>> {
+    let mut words = Vec::new();
+    let mut chunk_start = region.start;
+    for i in region.start..=region.end {
+        if i < region.end && !is_whitespace(graphemes[i]) {
+            continue;
+        }
+        let chunk = &graphemes[chunk_start..i];
+        let text = chunk.concat();
+        if !(text.contains("://") || text.contains('@') || text.contains('`')) {
+            words.extend(
+                chunk_words(chunk)
+                    .into_iter()
+                    .map(|word| word.start + chunk_start..word.end + chunk_start),
+            );
+        }
+        chunk_start = i + 1;
+    }
+    words
+}
+
+fn chunk_words(chunk: &[&str]) -> Vec<Range<usize>> {
+    let mut words = Vec::new();
+    let mut i = 0;
+    while i < chunk.len() {
+        if !is_word_part(chunk[i]) {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < chunk.len() && is_word_part(chunk[i]) {
+            i += 1;
+        }
+        let mut word = start..i;
+        while word.start < word.end && is_apostrophe(chunk[word.start]) {
+            word.start += 1;
+        }
+        while word.end > word.start && is_apostrophe(chunk[word.end - 1]) {
+            word.end -= 1;
+        }
+        let text = chunk[word.clone()].concat();
+        let letters = text.chars().filter(|c| c.is_alphabetic()).count();
+        let plain = text
+            .chars()
+            .all(|c| c.is_alphabetic() || is_apostrophe_char(c));
+        let inner_capital = text.chars().skip(1).any(char::is_uppercase);
+        if plain && letters > 1 && !inner_capital {
+            words.push(word);
+        }
+    }
+    words
+}
+
+fn is_word_part(grapheme: &str) -> bool {
+    grapheme
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == '_' || is_apostrophe_char(c))
+}
+
+fn is_apostrophe(grapheme: &str) -> bool {
+    grapheme.chars().all(is_apostrophe_char)
+}
+
+fn is_apostrophe_char(c: char) -> bool {
+    c == '\'' || c == '’'
+}
+
+fn is_whitespace(grapheme: &str) -> bool {
+    grapheme.chars().all(char::is_whitespace)
+}
+
+//optimal string alignment distance: insertions, deletions, substitutions and swapped neighbours
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = distance;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+//`known` written like `like`: all capitals, a capital first letter or as it is in the list
+fn match_case(known: &str, like: &str) -> String {
+    let letters = || like.chars().filter(|c| c.is_alphabetic());
+    if letters().count() > 1 && letters().all(char::is_uppercase) {
+        return known.to_uppercase();
+    }
+    if like.chars().next().is_some_and(char::is_uppercase) {
+        let mut chars = known.chars();
+        if let Some(first) = chars.next() {
+            return first.to_uppercase().chain(chars).collect();
+        }
+    }
+    known.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checker() -> SpellChecker {
+        SpellChecker::new(
+            "7\nthe\nsee\nquick\nbrown/MS\nfox\nreceive\nParis\n",
+            "txt\n",
+        )
+    }
+
+    fn misspelled(line: &str, file_name: &str) -> Vec<String> {
+        let graphemes: Vec<&str> = line.graphemes(true).collect();
+        checker()
+            .misspellings(line, &FileType::from(file_name))
+            .into_iter()
+            .map(|range| graphemes[range].concat())
+            .collect()
+    }
+
+    #[test]
+    fn finds_unknown_words_in_prose_comments_and_strings() {
+        assert_eq!(
+            misspelled(
+                "The quik brown fox's txt, see https://x.io `cde`",
+                "notes.md"
+            ),
+            ["quik", "fox's"]
+        );
+        assert_eq!(misspelled("paris Paris PARIS", "notes.txt"), ["paris"]);
+        assert_eq!(
+            misspelled(
+                "let brwn = \"the \\\"quik\\\" fox\"; // recieve my_var HashMap",
+                "a.rs"
+            ),
+            ["quik", "recieve"]
+        );
+        assert_eq!(misspelled("x = \"# fxo\" # teh", "a.py"), ["fxo", "teh"]);
+    }
+
+    #[test]
+    fn suggests_close_words_in_the_same_case() {
+        let checker = checker();
+        assert_eq!(checker.suggestions("recieve"), ["receive"]);
+        assert_eq!(checker.suggestions("Teh"), ["The", "See", "Txt"]);
+        //the forms the flags add are suggested too
+        assert_eq!(checker.suggestions("BRWON"), ["BROWN", "BROWNS"]);
+        assert!(checker.suggestions("elephant").is_empty());
+        assert!(checker.is_correct("Txt"));
+        assert_eq!(edit_distance(&['a', 'b'], &['b', 'a']), 1);
+    }
+
+    #[test]
+    fn hunspell_flags_add_inflected_forms() {
+        let checker = checker();
+        assert!(checker.is_correct("browns") && checker.is_correct("brown's"));
+        assert!(!checker.is_correct("foxes"));
+
+        let checker = SpellChecker::new("4\nfox/MS\nwork/ADJSGZRM\nhope/DGS\ncry/DGS\n", "");
+        for word in [
+            "foxes", "fox's", "works", "worked", "working", "workings", "worker",
+        ] {
+            assert!(checker.is_correct(word), "{}", word);
+        }
+        for word in [
+            "workers", "work's", "hoped", "hoping", "hopes", "cried", "crying", "cries",
+        ] {
+            assert!(checker.is_correct(word), "{}", word);
+        }
+        assert!(!checker.is_correct("hopeing") && !checker.is_correct("cryed"));
+    }
+}
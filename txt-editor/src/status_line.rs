@@ -0,0 +1,212 @@
+use crate::config::Config;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+const DEFAULT_LEFT: &str = "{file}{modified} - {lines} lines | {mode} | {selection} | {recording}";
+//the last segment is the one kept longest on a narrow terminal
+const DEFAULT_RIGHT: &str = "{type} | {encoding} | {eol} | {percent} | {line}:{column}";
+const SEPARATOR: &str = " | ";
+const ELLIPSIS: &str = "…";
+
+//the status bar layout from the `status.left` and `status.right` config keys.
+//Each is a list of segments separated by ` | `, with {placeholders} filled in when drawing.
+//A segment whose placeholders are all empty is left out.
+pub struct StatusLine {
+    left: Vec<String>,
+    right: Vec<String>,
+}
+
+impl StatusLine {
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(
+            config.get("status.left").unwrap_or(DEFAULT_LEFT),
+            config.get("status.right").unwrap_or(DEFAULT_RIGHT),
+        )
+    }
+
+    fn new(left: &str, right: &str) -> Self {
+        Self {
+            left: split_segments(left),
+            right: split_segments(right),
+        }
+    }
+
+    //fills the segments in and fits them into `width` columns: right segments go first starting
+    //with the leftmost, then left segments from the end, then the last right segment. What is
+    //left of the first one is shortened at the front, where a long path is least interesting.
+    pub fn render<F>(&self, width: usize, value: F) -> String
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let mut left = expand_segments(&self.left, &value);
+        let mut right = expand_segments(&self.right, &value);
+        let line_width = |left: &[String], right: &[String]| {
+            let used = joined(left).width() + joined(right).width();
+            //at least one space between the two sides
+            used + usize::from(!left.is_empty() && !right.is_empty())
+        };
+        while line_width(&left, &right) > width && right.len() > 1 {
+            right.remove(0);
+        }
+        while line_width(&left, &right) > width && left.len() > 1 {
+            left.pop();
+        }
+        if line_width(&left, &right) > width {
+            right.clear();
+        }
+        let left = shorten_front(&joined(&left), width);
+        let right = joined(&right);
+        let padding = width.saturating_sub(left.width() + right.width());
+        format!("{}{}{}", left, " ".repeat(padding), right)
+    }
+}
+
+fn split_segments(template: &str) -> Vec<String> {
+    template
+        .split(SEPARATOR.trim())
+        .map(str::trim)
+        .filter(|segment| !segment.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+fn expand_segments<F>(segments: &[String], value: &F) -> Vec<String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    segments
+        .iter()
+        .filter_map(|segment| expand(segment, value))
+        .collect()
+}
+
+//replaces known {placeholders}, unknown ones stay as they are.
+//None if the segment had placeholders and all of them were empty.
+fn expand<F>(template: &str, value: &F) -> Option<String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut result = String::new();
+    let mut rest = template;
+    let mut placeholders = 0;
+    let mut empty = 0;
+    while let Some(open) = rest.find('{') {
+        let close = match rest[open..].find('}') {
+            Some(close) => open + close,
+            None => break,
+        };
+        result.push_str(&rest[..open]);
+        match value(&rest[open + 1..close]) {
+            Some(text) => {
+                placeholders += 1;
+                if text.is_empty() {
+                    empty += 1;
+                }
+                result.push_str(&text);
+            }
+            None => result.push_str(&rest[open..=close]),
+        }
+        rest = &rest[close + 1..];
+    }
+    result.push_str(rest);
+    if placeholders > 0 && placeholders == empty {
+        None
+    } else {
+        Some(result)
+    }
+}
+
+fn joined(segments: &[String]) -> String {
+    segments.join(SEPARATOR)
+}
+
+fn shorten_front(text: &str, width: usize) -> String {
+    if text.width() <= width {
+        return text.to_string();
+    }
+    if width == 0 {
+        return String::new();
+    }
+    let mut kept = Vec::new();
+    let mut used = ELLIPSIS.width();
+    for grapheme in text.graphemes(true).rev() {
+        used += grapheme.width();
+        if used > width {
+            break;
+        }
+        kept.push(grapheme);
+    }
+    kept.push(ELLIPSIS);
+    kept.into_iter().rev().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(name: &str) -> Option<String> {
+        let text = match name {
+            "file" => "src/editor.rs",
+            "modified" => "*",
+            "lines" => "120",
+            "selection" => "",
+            "type" => "Rust",
+            "line" => "7",
+            _ => return None,
+        };
+        Some(text.to_string())
+    }
+
+    #[test]
+    fn drops_empty_segments_and_fits_narrow_terminals() {
+        let status = StatusLine::new(
+            "{file}{modified} | {selection} | x{unknown}",
+            "{type} | {line}/{lines}",
+        );
+        assert_eq!(
+            status.render(40, value),
+            "src/editor.rs* | x{unknown} Rust | 7/120"
+        );
+        assert_eq!(
+            status.render(36, value),
+            "src/editor.rs* | x{unknown}    7/120"
+        );
+        assert_eq!(status.render(22, value), "src/editor.rs*   7/120");
+        assert_eq!(status.render(12, value), "…/editor.rs*");
+    }
+
+    #[test]
+    fn default_layout_with_wide_file_names() {
+        let status = StatusLine::new(DEFAULT_LEFT, DEFAULT_RIGHT);
+        let value = |name: &str| {
+            let text = match name {
+                "file" => "文書.txt",
+                "modified" => "*",
+                "lines" => "3",
+                "mode" | "selection" | "recording" => "",
+                "type" => "Text",
+                "encoding" => "UTF-8",
+                "eol" => "LF",
+                "percent" => "Top",
+                "line" | "column" => "1",
+                _ => return None,
+            };
+            Some(text.to_string())
+        };
+        assert_eq!(
+            status.render(60, value),
+            format!(
+                "文書.txt* - 3 lines{}Text | UTF-8 | LF | Top | 1:1",
+                " ".repeat(12)
+            )
+        );
+        assert_eq!(
+            status.render(40, value),
+            format!("文書.txt* - 3 lines{}LF | Top | 1:1", " ".repeat(7))
+        );
+        //a wide character is never cut in half
+        assert_eq!(status.render(18, value), "…書.txt* - 3 lines");
+        assert_eq!(status.render(17, value), "….txt* - 3 lines ");
+        assert_eq!(status.render(10, value), "…- 3 lines");
+    }
+}
@@ -0,0 +1,26 @@
+use std::env;
+use std::fs;
+use std::io::Error;
+use std::path::PathBuf;
+
+//everything the editor remembers between runs lives in one directory,
+//$TXT_EDITOR_HOME if set, ~/.txt-editor otherwise
+pub fn data_file(name: &str) -> Option<PathBuf> {
+    let dir = match env::var_os("TXT_EDITOR_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".txt-editor"),
+    };
+    Some(dir.join(name))
+}
+
+pub fn read(name: &str) -> Option<String> {
+    fs::read_to_string(data_file(name)?).ok()
+}
+
+pub fn write(name: &str, contents: &str) -> Result<(), Error> {
+    let path = data_file(name).ok_or_else(|| Error::other("no home directory"))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, contents)
+}
@@ -1,3 +1,4 @@
+use std::fs::File;
 use std::io::Error;
 use std::io::{self, stdout, Write};
 use termion::{event::Key, input::TermRead, raw::IntoRawMode, color};
@@ -11,9 +12,12 @@ pub struct Size {
 pub struct Terminal {
     size: Size,
     _stdout: termion::raw::RawTerminal<io::Stdout>,
+    //when stdin is a pipe, keys are read from the controlling terminal instead
+    tty: Option<File>,
 }
 
 impl Terminal {
+    #[allow(clippy::should_implement_trait)]
     pub fn default() -> Result<Self, Error> {
         let size = termion::terminal_size()?;
         Ok(Self {
@@ -22,6 +26,11 @@ impl Terminal {
                 height: size.1.saturating_sub(2),
             },
             _stdout: stdout().into_raw_mode()?,
+            tty: if termion::is_tty(&io::stdin()) {
+                None
+            } else {
+                Some(termion::get_tty()?)
+            },
         })
     }
 
@@ -49,9 +58,13 @@ impl Terminal {
         io::stdout().flush()
     }
 
-    pub fn read_key() -> Result<Key, std::io::Error> {
+    pub fn read_key(&mut self) -> Result<Key, std::io::Error> {
         loop {
-            if let Some(key) = io::stdin().lock().keys().next() {
+            let key = match &mut self.tty {
+                Some(tty) => tty.keys().next(),
+                None => io::stdin().lock().keys().next(),
+            };
+            if let Some(key) = key {
                 return key;
             }
         }